@@ -0,0 +1,132 @@
+use std::collections::{
+    BTreeSet as Set,
+};
+use crate::rule::{
+    Rule,
+    resolve,
+    winning_rules,
+};
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Analysis<T> {
+    shadowed_rules: Vec<ShadowedRule<T>>,
+    nullable_rules: Vec<Rule<T>>,
+    ambiguous_rules: Vec<(Rule<T>, Rule<T>)>,
+    uncovered_ranges: Vec<(char, char)>,
+    state_count: usize,
+    transition_count: usize,
+}
+
+impl<T> Analysis<T> {
+    pub(crate) fn new(shadowed_rules: Vec<ShadowedRule<T>>, nullable_rules: Vec<Rule<T>>, ambiguous_rules: Vec<(Rule<T>, Rule<T>)>, uncovered_ranges: Vec<(char, char)>, state_count: usize, transition_count: usize) -> Analysis<T> {
+        Analysis { shadowed_rules, nullable_rules, ambiguous_rules, uncovered_ranges, state_count, transition_count }
+    }
+
+    // Rules which never produce a token, in production order.
+    pub fn shadowed_rules(&self) -> &[ShadowedRule<T>] {
+        &self.shadowed_rules
+    }
+
+    // Rules which match the empty string, in production order. The lexer never
+    // emits empty tokens, so these only match when they consume input.
+    pub fn nullable_rules(&self) -> &[Rule<T>] {
+        &self.nullable_rules
+    }
+
+    // Pairs of rules which match some token with the same priority, so the
+    // first rule of the pair wins by production order alone. Keywords
+    // written before a rule for identifiers are the usual example.
+    pub fn ambiguous_rules(&self) -> &[(Rule<T>, Rule<T>)] {
+        &self.ambiguous_rules
+    }
+
+    // Inclusive ranges of characters no rule can start with, so input
+    // containing them at a token boundary can never be lexed.
+    pub fn uncovered_ranges(&self) -> &[(char, char)] {
+        &self.uncovered_ranges
+    }
+
+    // The size of the minimized DFAs of every mode together.
+    pub fn state_count(&self) -> usize {
+        self.state_count
+    }
+
+    pub fn transition_count(&self) -> usize {
+        self.transition_count
+    }
+
+    // Whether no rule is shadowed or nullable. Ambiguous rules are not
+    // considered since production order resolves them, nor are uncovered
+    // ranges since most specs deliberately leave characters unhandled.
+    pub fn is_clean(&self) -> bool {
+        self.shadowed_rules.is_empty() && self.nullable_rules.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct ShadowedRule<T> {
+    rule: Rule<T>,
+    shadowed_by: Vec<Rule<T>>,
+}
+
+impl<T> ShadowedRule<T> {
+    pub fn rule(&self) -> &Rule<T> {
+        &self.rule
+    }
+
+    // The rules that win every final state the shadowed rule reaches. This is
+    // empty when the rule matches nothing at all.
+    pub fn shadowed_by(&self) -> &[Rule<T>] {
+        &self.shadowed_by
+    }
+}
+
+// A rule is shadowed when, in every final state it accepts, some other rule
+// takes precedence over it. Precedence goes to the highest priority and then
+// to the earliest production, if the rules are ordered. Rules in conflict
+// are not shadowed, since lexing reports the conflict instead. The
+// acceptances are the rule sets of the final states of a mode's DFA before
+// minimization, other than its initial state, which never produces a token.
+// Only the given rules, those of the mode, are checked.
+pub(crate) fn shadowed_rules<T: Clone + PartialEq>(rules: &[Rule<T>], rule_indices: &[usize], acceptances: &Set<Set<usize>>, ordered: bool) -> Vec<ShadowedRule<T>> {
+    let mut shadowed_rules = Vec::new();
+    for &rule_index in rule_indices {
+        let rule = &rules[rule_index];
+        let mut winners = Set::new();
+        for state_rule_indices in acceptances {
+            if state_rule_indices.contains(&rule_index) {
+                match resolve(rules, state_rule_indices, ordered) {
+                    Ok(winner) => winners.extend(winner),
+                    Err(_) => winners.extend(winning_rules(rules, state_rule_indices)),
+                }
+            }
+        }
+        if !winners.contains(&rule_index) {
+            shadowed_rules.push(ShadowedRule {
+                rule: rule.clone(),
+                shadowed_by: winners.into_iter().map(|winner| rules[winner].clone()).collect(),
+            });
+        }
+    }
+    shadowed_rules
+}
+
+pub(crate) fn nullable_rules<T: Clone>(rules: &[Rule<T>], nullable: &Set<usize>) -> Vec<Rule<T>> {
+    nullable.iter().map(|&rule_index| rules[rule_index].clone()).collect()
+}
+
+// Pairs of rule indices, winner first, which tie on priority in some final
+// state. Only ordered rules are resolved by order.
+pub(crate) fn ambiguous_rules<T>(rules: &[Rule<T>], acceptances: &Set<Set<usize>>, ordered: bool) -> Set<(usize, usize)> {
+    let mut ambiguous_rules = Set::new();
+    if !ordered {
+        return ambiguous_rules;
+    }
+    for rule_indices in acceptances {
+        let winners = winning_rules(rules, rule_indices);
+        for &loser in &winners[1..] {
+            ambiguous_rules.insert((winners[0], loser));
+        }
+    }
+    ambiguous_rules
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::{
+        BTreeSet as Set,
+        BTreeMap as Map,
+        VecDeque,
+    },
+    fmt::Debug,
+};
+use crate::{
+    minimize::live_states,
+    mode::{
+        Mode,
+        nfa,
+    },
+    rule::{
+        Rule,
+        resolve,
+    },
+    segments::{
+        bounds,
+        complement,
+    },
+};
+
+// Options for the DOT export of a lexer's automata.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DotOptions {
+    collapse_above: Option<usize>,
+}
+
+impl DotOptions {
+    pub fn new() -> DotOptions {
+        DotOptions { collapse_above: None }
+    }
+
+    // Edges labelled with more than the given number of character ranges are
+    // labelled with their complement if that is short enough, or otherwise
+    // with just the number of ranges.
+    pub fn collapse_above(mut self, ranges: usize) -> DotOptions {
+        self.collapse_above = Some(ranges);
+        self
+    }
+}
+
+// The inclusive character ranges an edge is taken on, where None stands for
+// an epsilon transition.
+type Ranges = Vec<Option<(u32, u32)>>;
+
+// The states, final states and edges of an automaton.
+struct Graph {
+    initial_index: usize,
+    states: Map<usize, (bool, Vec<String>)>,
+    edges: Map<(usize, usize), Ranges>,
+}
+
+pub(crate) fn dfa_to_dot<T: Clone + Debug + Ord>(rules: &[Rule<T>], modes: &Map<String, Mode<T>>, options: &DotOptions) -> String {
+    let mut graphs = Vec::new();
+    for (name, mode) in modes {
+        let number = |state_index| mode.numbers[&state_index];
+        let mut graph = Graph { initial_index: number(mode.dfa.initial_index()), states: Map::new(), edges: Map::new() };
+        for state_index in live_states(&mode.dfa) {
+            // the rules accepted by the states merged into this one, with the
+            // rule which takes precedence first, unless the rules are in
+            // conflict. Each rule gets a line of its own, unless the merged
+            // states accept different rules, which share a line separated by
+            // bars.
+            let acceptances = mode.merged.get(&state_index).into_iter().flatten()
+                .filter_map(|unminimized_index| mode.unminimized_accepting.get(unminimized_index))
+                .map(|rule_indices| {
+                    let winner = resolve(rules, rule_indices, mode.ordered).ok().flatten();
+                    winner.into_iter().chain(rule_indices.iter().cloned().filter(|&rule_index| Some(rule_index) != winner)).map(|rule_index| rules[rule_index].to_string()).collect::<Vec<_>>()
+                })
+                .collect::<Set<_>>();
+            let names = match acceptances.len() {
+                1 => acceptances.into_iter().flatten().collect(),
+                _ => vec![acceptances.into_iter().map(|names| names.join(", ")).collect::<Vec<_>>().join(" | ")],
+            };
+            graph.states.insert(number(state_index), (mode.dfa.is_final(state_index), names));
+            for transition_index in mode.dfa.transition_indices_from(state_index) {
+                let (_, segment, target_index) = mode.dfa.transitions_index(transition_index);
+                graph.edges.entry((number(state_index), number(target_index))).or_default().push(bounds(segment));
+            }
+        }
+        graphs.push((name.as_str(), graph));
+    }
+    render("dfa", &graphs, options)
+}
+
+// The automaton is rebuilt, since lexers only keep the minimized DFA.
+pub(crate) fn nfa_to_dot<T: Clone + Debug + Ord>(rules: &[Rule<T>], modes: &Map<String, Mode<T>>, options: &DotOptions) -> String {
+    let mut graphs = Vec::new();
+    for (name, mode) in modes {
+        let (enfa, rule_indices) = nfa(rules, &mode.rule_indices);
+        // states are numbered in the order they are visited
+        let mut graph = Graph { initial_index: 0, states: Map::new(), edges: Map::new() };
+        let mut numbers = Map::new();
+        let mut queue = VecDeque::new();
+        numbers.insert(enfa.initial_index(), 0);
+        queue.push_back(enfa.initial_index());
+        while let Some(state_index) = queue.pop_front() {
+            let names = rule_indices.get(enfa.states_index(state_index)).map(|&rule_index| rules[rule_index].to_string()).into_iter().collect();
+            graph.states.insert(numbers[&state_index], (enfa.is_final(state_index), names));
+            for transition_index in enfa.transition_indices_from(state_index) {
+                let (_, segment, target_index) = enfa.transitions_index(transition_index);
+                if !numbers.contains_key(&target_index) {
+                    numbers.insert(target_index, numbers.len());
+                    queue.push_back(target_index);
+                }
+                graph.edges.entry((numbers[&state_index], numbers[&target_index])).or_default().push(bounds(segment));
+            }
+        }
+        graphs.push((name.as_str(), graph));
+    }
+    render("nfa", &graphs, options)
+}
+
+// Each mode is drawn as a cluster of its own.
+fn render(name: &str, graphs: &[(&str, Graph)], options: &DotOptions) -> String {
+    let mut dot = format!("digraph {} {{\n    rankdir=LR;\n", name);
+    for (mode_index, (mode, graph)) in graphs.iter().enumerate() {
+        dot.push_str(&format!("    subgraph cluster_{} {{\n        label=\"{}\";\n", mode_index, escape(mode)));
+        dot.push_str(&format!("        start_{} [shape=point];\n", mode_index));
+        for (state_index, (is_final, names)) in &graph.states {
+            let mut label = state_index.to_string();
+            for name in names {
+                label.push('\n');
+                label.push_str(name);
+            }
+            let shape = if *is_final { "doublecircle" } else { "circle" };
+            dot.push_str(&format!("        s{}_{} [label=\"{}\", shape={}];\n", mode_index, state_index, escape(&label), shape));
+        }
+        dot.push_str(&format!("        start_{} -> s{}_{};\n", mode_index, mode_index, graph.initial_index));
+        for ((source_index, target_index), ranges) in &graph.edges {
+            dot.push_str(&format!("        s{}_{} -> s{}_{} [label=\"{}\"];\n", mode_index, source_index, mode_index, target_index, escape(&label(ranges, options))));
+        }
+        dot.push_str("    }\n");
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn label(ranges: &[Option<(u32, u32)>], options: &DotOptions) -> String {
+    let mut labels = Vec::new();
+    if ranges.iter().any(Option::is_none) {
+        labels.push(String::from("ε"));
+    }
+    let mut merged: Vec<(u32, u32)> = Vec::new();
+    let mut sorted: Vec<(u32, u32)> = ranges.iter().flatten().cloned().collect();
+    sorted.sort();
+    for (lower, upper) in sorted {
+        match merged.last_mut() {
+            Some((_, last_upper)) if u64::from(lower) <= u64::from(*last_upper) + 1 => *last_upper = (*last_upper).max(upper),
+            _ => merged.push((lower, upper)),
+        }
+    }
+    if merged.is_empty() {
+        return labels.join(" ");
+    }
+    let class = match options.collapse_above {
+        Some(limit) if merged.len() > limit => {
+            let uncovered: Vec<(u32, u32)> = complement(merged.clone()).into_iter().map(|(lower, upper)| (u32::from(lower), u32::from(upper))).collect();
+            if uncovered.len() <= limit {
+                format!("[^{}]", ranges_text(&uncovered))
+            } else {
+                format!("{} ranges", merged.len())
+            }
+        },
+        _ if merged.len() == 1 && merged[0].0 == merged[0].1 => character_text(merged[0].0),
+        _ => format!("[{}]", ranges_text(&merged)),
+    };
+    labels.push(class);
+    labels.join(" ")
+}
+
+fn ranges_text(ranges: &[(u32, u32)]) -> String {
+    let mut text = String::new();
+    for &(lower, upper) in ranges {
+        text.push_str(&character_text(lower));
+        if upper > lower {
+            text.push('-');
+            text.push_str(&character_text(upper));
+        }
+    }
+    text
+}
+
+fn character_text(value: u32) -> String {
+    match std::char::from_u32(value) {
+        Some(character) if !character.is_control() && !character.is_whitespace() && !"[]^-\\".contains(character) => character.to_string(),
+        _ => format!("\\u{{{:X}}}", value),
+    }
+}
+
+// Escapes a string for a quoted DOT attribute.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
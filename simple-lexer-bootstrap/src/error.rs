@@ -0,0 +1,50 @@
+use std::{
+    borrow::Cow,
+    fmt,
+};
+use crate::span::Span;
+
+// An error lexing text. The span runs from the start of the token being lexed
+// up to and including the character which could not be consumed. With the
+// serde feature, an error serializes as `{"message": "...", "span": ...}`.
+// Messages the lexer produces are static, while deserialized ones are owned.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Error {
+    message: Cow<'static, str>,
+    span: Span,
+}
+
+impl Error {
+    pub fn new<M: Into<Cow<'static, str>>>(message: M, span: Span) -> Error {
+        Error { message: message.into(), span }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.span.start().line(), self.span.start().column(), self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Lexing used to fail with just a static message, so callers returning one
+// can still use `?`. Only deserialized errors have a message which is not
+// static, and those fall back to a generic one.
+impl From<Error> for &'static str {
+    fn from(error: Error) -> &'static str {
+        match error.message {
+            Cow::Borrowed(message) => message,
+            Cow::Owned(_) => "lexing failed",
+        }
+    }
+}
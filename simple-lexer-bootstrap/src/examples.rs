@@ -0,0 +1,55 @@
+use std::collections::{
+    BTreeMap as Map,
+    VecDeque,
+};
+use crate::{
+    mode::Mode,
+    rule::Rule,
+    segments::{
+        bounds,
+        character,
+    },
+};
+
+// Up to count of the shortest strings each rule wins when lexed on their own
+// in the rule's mode, in rule order. The search is breadth first through the
+// DFA of each mode, visiting each state at most count times and taking one
+// character from each transition, so strings only differ in their path
+// through the DFA.
+pub(crate) fn examples<T: Clone + Ord>(rules: &[Rule<T>], modes: &Map<String, Mode<T>>, count: usize) -> Vec<Vec<String>> {
+    let mut examples = vec![Vec::new(); rules.len()];
+    for mode in modes.values() {
+        let initial_index = mode.dfa.initial_index();
+        let mut visits = Map::new();
+        let mut queue = VecDeque::new();
+        queue.push_back((initial_index, String::new()));
+        while let Some((state_index, text)) = queue.pop_front() {
+            if state_index != initial_index {
+                if let Ok(Some(winner)) = mode.winner(rules, state_index) {
+                    let rule_examples = &mut examples[winner];
+                    if rule_examples.len() < count {
+                        rule_examples.push(text.clone());
+                    }
+                }
+            }
+            let mut steps = Vec::new();
+            for transition_index in mode.dfa.transition_indices_from(state_index) {
+                let (_, segment, target_index) = mode.dfa.transitions_index(transition_index);
+                if let Some(character) = bounds(segment).and_then(|(lower, upper)| character(lower, upper, true, |_| 0)) {
+                    steps.push((character, target_index));
+                }
+            }
+            steps.sort();
+            for (character, target_index) in steps {
+                let target_visits = visits.entry(target_index).or_insert(0);
+                if *target_visits < count {
+                    *target_visits += 1;
+                    let mut target_text = text.clone();
+                    target_text.push(character);
+                    queue.push_back((target_index, target_text));
+                }
+            }
+        }
+    }
+    examples
+}
@@ -1,32 +1,75 @@
-use std::collections::{
-    BTreeSet as Set,
-    BTreeMap as Map,
-    VecDeque,
-};
-use segment_map::Segment;
-use finite_automata::{
-    Enfa,
-    Dfa,
-    Subsume,
-    states_contains_from,
+use std::{
+    cmp::Ordering,
+    collections::{
+        BTreeSet as Set,
+        BTreeMap as Map,
+        VecDeque,
+    },
+    fmt::Debug,
 };
+use finite_automata::StateIndex;
 use regular_expression_bootstrap::Expression;
 use crate::{
-    TokenState,
-    TokenStateGenerator,
+    analysis::{
+        Analysis,
+        shadowed_rules,
+        nullable_rules,
+        ambiguous_rules,
+    },
+    minimize::live_states,
+    segments::{
+        bounds,
+        complement,
+    },
+    span::{
+        Location,
+        Span,
+    },
+    rule::Rule,
+    mode::{
+        Mode,
+        DEFAULT_MODE,
+    },
+    dot::{
+        DotOptions,
+        dfa_to_dot,
+        nfa_to_dot,
+    },
+    trace::Step,
+    sample::{
+        SampleOptions,
+        SeededRng,
+        pick_rule,
+        distances,
+        walk,
+    },
+    examples::examples,
+    error::Error,
 };
 
-type Result<T> = std::result::Result<T, &'static str>;
+// The tokens lexed from some text, or where lexing it failed.
+type Lexed<T> = std::result::Result<Vec<Token<T>>, Error>;
 
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+// Tokens compare by kind and text alone, so a token built without a span
+// still equals the same token produced by the lexer, while eq_with_span also
+// compares spans. With the serde feature, a token serializes as
+// `{"kind": ..., "text": "...", "span": ...}`, with the kind in whatever form T
+// takes.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token<T> {
     kind: T,
     text: String,
+    span: Span,
 }
 
 impl<T> Token<T> {
     pub fn new(kind: T, text: &str) -> Token<T> {
-        Token { kind, text: String::from(text) }
+        Token { kind, text: String::from(text), span: Span::default() }
+    }
+
+    pub fn with_span(kind: T, text: &str, span: Span) -> Token<T> {
+        Token { kind, text: String::from(text), span }
     }
 
     pub fn kind(&self) -> &T {
@@ -36,84 +79,298 @@ impl<T> Token<T> {
     pub fn text(&self) -> &str {
         &self.text
     }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    // Like ==, but the spans must match as well.
+    pub fn eq_with_span(&self, other: &Token<T>) -> bool where T: PartialEq {
+        self == other && self.span == other.span
+    }
+}
+
+impl<T: PartialEq> PartialEq for Token<T> {
+    fn eq(&self, other: &Token<T>) -> bool {
+        self.kind == other.kind && self.text == other.text
+    }
+}
+
+impl<T: Eq> Eq for Token<T> {}
+
+impl<T: PartialOrd> PartialOrd for Token<T> {
+    fn partial_cmp(&self, other: &Token<T>) -> Option<Ordering> {
+        (&self.kind, &self.text).partial_cmp(&(&other.kind, &other.text))
+    }
+}
+
+impl<T: Ord> Ord for Token<T> {
+    fn cmp(&self, other: &Token<T>) -> Ordering {
+        (&self.kind, &self.text).cmp(&(&other.kind, &other.text))
+    }
 }
 
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Lexer<T> {
-    productions: Map<Expression, Option<T>>,
-    dfa: Dfa<Set<TokenState<T>>, u32>
+    rules: Vec<Rule<T>>,
+    modes: Map<String, Mode<T>>,
 }
 
 impl<T: Clone + Ord> Lexer<T> {
+    // Builds a lexer from productions keyed by expression. Their ids follow
+    // the order of the expressions, which breaks no ties: productions which
+    // match the same token with different kinds fail to lex it with
+    // "inconsistent tokens in final state".
     pub fn new(productions: Map<Expression, Option<T>>) -> Lexer<T> {
-        let mut fas = Vec::new();
-        for (expression, token) in &productions {
-            fas.push(expression.as_enfa(&mut TokenStateGenerator::new(token.clone())));
+        Lexer::build(productions.into_iter().map(|(expression, token_kind)| Rule::new(expression, token_kind)).collect(), false)
+    }
+
+    // Builds a lexer from rules in production order. Each rule's id is set to
+    // its position, and the earliest rule wins ties in priority. Each mode
+    // gets its own automaton over the rules active in it. A mode is defined
+    // once some rule is active in it, and switching to an undefined mode
+    // fails with "undefined mode".
+    pub fn from_rules(rules: Vec<Rule<T>>) -> std::result::Result<Lexer<T>, &'static str> {
+        for next_mode in rules.iter().filter_map(Rule::next_mode) {
+            if next_mode != DEFAULT_MODE && !rules.iter().any(|rule| rule.mode() == next_mode) {
+                return Err("undefined mode");
+            }
         }
-        let mut alt = Enfa::new(TokenState::new(None));
-        for fa in fas {
-            alt.subsume(&fa);
-            let fa_initial_index = states_contains_from(&alt, &fa, fa.initial_index()).expect("state does not exist");
-            alt.transitions_insert((alt.initial_index(), Segment::empty(), fa_initial_index));
-            for fa_final_index in fa.final_indices() {
-                let fa_final_index = states_contains_from(&alt, &fa, fa_final_index).expect("state does not exist");
-                alt.set_final(fa_final_index);
+        Ok(Lexer::build(rules, true))
+    }
+
+    fn build(rules: Vec<Rule<T>>, ordered: bool) -> Lexer<T> {
+        let rules: Vec<Rule<T>> = rules.into_iter().enumerate().map(|(id, rule)| rule.with_id(id)).collect();
+        let mut mode_rule_indices = Map::new();
+        mode_rule_indices.insert(String::from(DEFAULT_MODE), Vec::new());
+        for (rule_index, rule) in rules.iter().enumerate() {
+            mode_rule_indices.entry(String::from(rule.mode())).or_insert_with(Vec::new).push(rule_index);
+        }
+        let modes = mode_rule_indices.into_iter().map(|(name, rule_indices)| (name, Mode::new(&rules, rule_indices, ordered))).collect();
+        Lexer { rules, modes }
+    }
+
+    pub fn rules(&self) -> &[Rule<T>] {
+        &self.rules
+    }
+
+    // The names of the modes, which include the default mode and every mode a
+    // rule is active in.
+    pub fn modes(&self) -> impl Iterator<Item = &str> {
+        self.modes.keys().map(String::as_str)
+    }
+
+    // Rules are only compared with rules of the same mode. Coverage is that of
+    // the default mode, where lexing starts.
+    pub fn analyze(&self) -> Analysis<T> {
+        let mut shadowed = Vec::new();
+        let mut nullable = Vec::new();
+        let mut ambiguous = Set::new();
+        let mut state_count = 0;
+        let mut transition_count = 0;
+        for mode in self.modes.values() {
+            shadowed.extend(shadowed_rules(&self.rules, &mode.rule_indices, &mode.acceptances, mode.ordered));
+            nullable.extend(nullable_rules(&self.rules, &mode.nullable));
+            ambiguous.extend(ambiguous_rules(&self.rules, &mode.acceptances, mode.ordered));
+            for state_index in live_states(&mode.dfa) {
+                state_count += 1;
+                transition_count += mode.dfa.transition_indices_from(state_index).count();
             }
         }
-        Lexer { productions, dfa: Dfa::from(&alt) }
+        shadowed.sort_by_key(|shadowed_rule| shadowed_rule.rule().id());
+        nullable.sort_by_key(Rule::id);
+        let ambiguous = ambiguous.into_iter().map(|(winner, loser)| (self.rules[winner].clone(), self.rules[loser].clone())).collect();
+        let mode = &self.modes[DEFAULT_MODE];
+        let mut covered = Vec::new();
+        for transition_index in mode.dfa.transition_indices_from(mode.dfa.initial_index()) {
+            let (_, segment, _) = mode.dfa.transitions_index(transition_index);
+            covered.extend(bounds(segment));
+        }
+        Analysis::new(shadowed, nullable, ambiguous, complement(covered), state_count, transition_count)
+    }
+
+    // Lexing starts in the default mode, and a rule with a next mode switches
+    // to it once its token is matched.
+    pub fn lex(&self, text: &str) -> Lexed<T> {
+        self.run(text, DEFAULT_MODE, Location::default(), None)
+    }
+
+    // Lexes the text like lex, also returning every transition taken up to
+    // the end of the text or the first error.
+    pub fn trace(&self, text: &str) -> (Vec<Step<T>>, Lexed<T>) {
+        let mut steps = Vec::new();
+        let tokens = self.run(text, DEFAULT_MODE, Location::default(), Some(&mut steps));
+        (steps, tokens)
     }
 
-    pub fn lex(&self, text: &str) -> Result<Vec<Token<T>>> {
+    // Lexes text which starts in the given mode at the given location, so
+    // that text can be picked up partway through.
+    fn run(&self, text: &str, mode_name: &str, start: Location, mut steps: Option<&mut Vec<Step<T>>>) -> Lexed<T> {
         let mut tokens = Vec::new();
         let mut token_text = String::from("");
         let mut characters: VecDeque<char> = text.chars().collect();
-        let mut source_index = self.dfa.initial_index();
+        let mut mode_name = mode_name;
+        let mut mode = &self.modes[mode_name];
+        let mut source_index = mode.dfa.initial_index();
+        // the state of the DFA before minimization, which a trace follows
+        // for the rules alive in it
+        let mut unminimized_index = mode.unminimized.initial_index();
+        let mut token_start = start;
+        let mut token_end = start;
         while let Some(character) = characters.pop_front() {
-            if let Some(transition_index) = self.dfa.transitions_contains_outgoing((source_index, &character.into())) {
-                let (_, _, target_index) = self.dfa.transitions_index(transition_index);
+            if let Some(transition_index) = mode.dfa.transitions_contains_outgoing((source_index, &character.into())) {
+                let (_, _, target_index) = mode.dfa.transitions_index(transition_index);
+                if let Some(steps) = steps.as_mut() {
+                    let unminimized_transition_index = mode.unminimized.transitions_contains_outgoing((unminimized_index, &character.into())).expect("transition does not exist");
+                    let (_, _, unminimized_target_index) = mode.unminimized.transitions_index(unminimized_transition_index);
+                    unminimized_index = unminimized_target_index;
+                    let alive_rules = mode.alive.get(&unminimized_index).into_iter().flatten().map(|&rule_index| self.rules[rule_index].clone()).collect();
+                    steps.push(Step::new(mode_name, character, token_end, mode.numbers[&source_index], mode.numbers[&target_index], token_text.is_empty(), alive_rules));
+                }
                 token_text.push(character);
+                token_end.advance(character);
                 source_index = target_index;
             } else {
-                if self.dfa.is_final(source_index) {
-                    let mut token_kind = &None;
-                    for token_state in self.dfa.states_index(source_index) {
-                        if token_kind.is_none() {
-                            token_kind = token_state.token_kind();
-                        } else {
-                            if token_state.token_kind().is_some() && token_state.token_kind() != token_kind {
-                                return Err("inconsistent tokens in final state");
-                            }
+                // a token must consume at least one character, even when a
+                // nullable rule makes the initial state final
+                if !token_text.is_empty() && mode.dfa.is_final(source_index) {
+                    let span = Span::new(token_start, token_end);
+                    if let Some(rule) = self.winning_rule(mode, source_index).map_err(|message| Error::new(message, span))? {
+                        if let Some(token_kind) = rule.token_kind() {
+                            tokens.push(Token::with_span(token_kind.clone(), token_text.as_str(), span));
+                        }
+                        if let Some(next_mode) = rule.next_mode() {
+                            let (next_mode_name, next_mode) = self.modes.get_key_value(next_mode).expect("undefined mode");
+                            mode_name = next_mode_name.as_str();
+                            mode = next_mode;
                         }
-                    }
-                    if let Some(token_kind) = token_kind {
-                        tokens.push(Token::new(token_kind.clone(), token_text.as_str()));
                     }
                     token_text.clear();
+                    token_start = token_end;
                     characters.push_front(character);
-                    source_index = self.dfa.initial_index();
-                } else { return Err("partial match"); }
-            }
-        }
-        if self.dfa.is_final(source_index) {
-            let mut token_kind = &None;
-            for token_state in self.dfa.states_index(source_index) {
-                if token_kind.is_none() {
-                    token_kind = token_state.token_kind();
+                    source_index = mode.dfa.initial_index();
+                    unminimized_index = mode.unminimized.initial_index();
                 } else {
-                    if token_state.token_kind().is_some() && token_state.token_kind() != token_kind {
-                        return Err("inconsistent tokens in final state");
-                    }
+                    let mut error_end = token_end;
+                    error_end.advance(character);
+                    return Err(Error::new("partial match", Span::new(token_start, error_end)));
                 }
             }
-            if let Some(token_kind) = token_kind {
-                tokens.push(Token::new(token_kind.clone(), token_text.as_str()));
+        }
+        if token_text.is_empty() {
+            // only empty input ends without a pending token
+        } else if mode.dfa.is_final(source_index) {
+            let span = Span::new(token_start, token_end);
+            if let Some(token_kind) = self.winning_rule(mode, source_index).map_err(|message| Error::new(message, span))?.and_then(|rule| rule.token_kind().as_ref()) {
+                tokens.push(Token::with_span(token_kind.clone(), token_text.as_str(), Span::new(token_start, token_end)));
             }
-        } else { return Err("partial match"); }
+        } else { return Err(Error::new("partial match", Span::new(token_start, token_end))); }
         Ok(tokens)
     }
+
+    // Random text which lexes to the returned tokens. Each token comes from a
+    // weighted pick among the rules of the current mode and a random walk
+    // through its DFA, and is only kept if the previous match and the new
+    // one still lex to themselves, so the maximal munch of one token never
+    // swallows the next. A match only ends where the next character has no
+    // transition, so earlier matches cannot change.
+    pub fn sample(&self, rng: &mut SeededRng, options: &SampleOptions<T>) -> (String, Vec<Token<T>>) {
+        const ATTEMPTS: usize = 16;
+        let rule_distances = self.modes.iter().flat_map(|(name, mode)| {
+            mode.rule_indices.iter().map(move |&rule_index| ((name.as_str(), rule_index), distances(&self.rules, mode, rule_index)))
+        }).collect::<Map<_, _>>();
+        let mut text = String::new();
+        let mut tokens = Vec::new();
+        let mut end = Location::default();
+        let mut mode_name = DEFAULT_MODE;
+        // where the previous match starts, its mode and the tokens before it
+        let mut previous = None;
+        for _ in 0..options.max_tokens() {
+            let mode = &self.modes[mode_name];
+            let (start, start_location, start_mode_name, kept) = previous.unwrap_or((text.len(), end, mode_name, tokens.len()));
+            let mut sampled = None;
+            for _ in 0..ATTEMPTS {
+                let rule_index = match pick_rule(&self.rules, &mode.rule_indices, options, rng) {
+                    Some(rule_index) => rule_index,
+                    None => break,
+                };
+                let token_text = match walk(mode, &rule_distances[&(mode_name, rule_index)], options.max_token_length(), rng) {
+                    Some(token_text) => token_text,
+                    None => continue,
+                };
+                let mut expected = tokens[kept..].to_vec();
+                if let Some(token_kind) = self.rules[rule_index].token_kind() {
+                    expected.push(Token::new(token_kind.clone(), &token_text));
+                }
+                let candidate = format!("{}{}", &text[start..], token_text);
+                match self.run(&candidate, start_mode_name, start_location, None) {
+                    Ok(lexed) if lexed == expected => {
+                        sampled = Some((rule_index, token_text, lexed));
+                        break;
+                    },
+                    _ => {},
+                }
+            }
+            let (rule_index, token_text, lexed) = match sampled {
+                Some(sampled) => sampled,
+                None => break,
+            };
+            tokens.truncate(kept);
+            tokens.extend(lexed);
+            let produced = if self.rules[rule_index].token_kind().is_some() { 1 } else { 0 };
+            previous = Some((text.len(), end, mode_name, tokens.len() - produced));
+            text.push_str(&token_text);
+            for character in token_text.chars() {
+                end.advance(character);
+            }
+            if let Some(next_mode) = self.rules[rule_index].next_mode() {
+                mode_name = self.modes.get_key_value(next_mode).map(|(next_mode_name, _)| next_mode_name.as_str()).expect("undefined mode");
+            }
+        }
+        (text, tokens)
+    }
+
+    // The shortest string each rule produces a token for, or None for rules
+    // which never win, in rule order. Rules outside the default mode only
+    // produce their token once the lexer is in their mode.
+    pub fn examples(&self) -> Vec<Option<String>> {
+        self.examples_up_to(1).into_iter().map(|rule_examples| rule_examples.into_iter().next()).collect()
+    }
+
+    // Up to count short strings for each rule, shortest first.
+    pub fn examples_up_to(&self, count: usize) -> Vec<Vec<String>> {
+        examples(&self.rules, &self.modes, count)
+    }
+
+    // The rule which takes precedence in a final state of a mode.
+    fn winning_rule(&self, mode: &Mode<T>, state_index: StateIndex) -> Result<Option<&Rule<T>>, &'static str> {
+        Ok(mode.winner(&self.rules, state_index)?.map(|winner| &self.rules[winner]))
+    }
+}
+
+impl<T: Clone + Debug + Ord> Lexer<T> {
+    // The minimized DFA of every mode in Graphviz DOT.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(&DotOptions::new())
+    }
+
+    pub fn to_dot_with(&self, options: &DotOptions) -> String {
+        dfa_to_dot(&self.rules, &self.modes, options)
+    }
+
+    // The NFA of every mode, the alternation of its rules before
+    // determinization, in Graphviz DOT.
+    pub fn nfa_to_dot(&self) -> String {
+        self.nfa_to_dot_with(&DotOptions::new())
+    }
+
+    pub fn nfa_to_dot_with(&self, options: &DotOptions) -> String {
+        nfa_to_dot(&self.rules, &self.modes, options)
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::upper_case_acronyms)]
 mod tests {
     use regular_expression_bootstrap::{
         sym,
@@ -126,10 +383,18 @@ mod tests {
         ast,
     };
     use crate::{
+        minimize::live_states,
+        mode::DEFAULT_MODE,
+        DotOptions,
+        SampleOptions,
+        SeededRng,
         Lexer,
+        Location,
+        Rule,
         Token,
     };
-    use super::Result;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
     #[test]
     fn test_1() -> Result<()> {
@@ -137,7 +402,7 @@ mod tests {
         enum TokenKind {
             A,
             B,
-        };
+        }
         use TokenKind::*;
         let lexer = Lexer::new(map![
             sym![sgl!('A')] => Some(A),
@@ -161,7 +426,7 @@ mod tests {
         enum TokenKind {
             A_REP,
             B_REP
-        };
+        }
         use TokenKind::*;
         let lexer = Lexer::new(map![
             ast!(sym![sgl!('A')]) => Some(A_REP),
@@ -187,7 +452,7 @@ mod tests {
             AB,
             BB,
             B,
-        };
+        }
         use TokenKind::*;
         let lexer = Lexer::new(map![
             sym![sgl!('A')] => Some(A),
@@ -276,4 +541,393 @@ mod tests {
         assert_eq!(expected, actual);
         Ok(())
     }
+
+    #[test]
+    fn test_5() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            X,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::new(map![
+            con![sym![sgl!('a')], sym![sgl!('c')]] => Some(X),
+            con![sym![sgl!('b')], sym![sgl!('c')]] => Some(X)
+        ]);
+        assert_eq!(3, live_states(&lexer.modes[DEFAULT_MODE].dfa).len());
+        let expected = vec![
+            Token::new(X, "ac"),
+            Token::new(X, "bc"),
+        ];
+        let actual = lexer.lex("acbc")?;
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_6() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            X,
+            Y,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(sym![sgl!('a')], Some(X)),
+            Rule::new(alt![sym![sgl!('a')], sym![sgl!('a')]], Some(X)),
+            Rule::new(sym![sgl!('b')], Some(Y))
+        ])?;
+        let analysis = lexer.analyze();
+        assert_eq!(1, analysis.shadowed_rules().len());
+        assert_eq!(1, analysis.shadowed_rules()[0].rule().id());
+        assert_eq!(vec![0], analysis.shadowed_rules()[0].shadowed_by().iter().map(Rule::id).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_7() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        #[allow(non_camel_case_types)]
+        enum TokenKind {
+            A_REP,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::new(map![
+            ast!(sym![sgl!('A')]) => Some(A_REP)
+        ]);
+        assert_eq!(1, lexer.analyze().nullable_rules().len());
+        assert_eq!(Vec::<Token<TokenKind>>::new(), lexer.lex("")?);
+        let error = lexer.lex("AB").err().ok_or("no error")?;
+        assert_eq!("partial match", error.message());
+        assert_eq!(&Location::new(1, 1, 2), error.span().start());
+        assert_eq!(&Location::new(2, 1, 3), error.span().end());
+        Ok(())
+    }
+
+    #[test]
+    fn test_8() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            X,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::new(map![
+            sym![rng!('b', 'y')] => Some(X)
+        ]);
+        let expected = [
+            ('\u{0}', 'a'),
+            ('z', '\u{D7FF}'),
+            ('\u{E000}', '\u{10FFFF}'),
+        ];
+        assert_eq!(expected, lexer.analyze().uncovered_ranges());
+        Ok(())
+    }
+
+    #[test]
+    fn test_9() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            A,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::new(map![
+            con![sym![sgl!('A')], ast!(sym![sgl!('A')])] => Some(A),
+            sym![sgl!('\n'), sgl!(' ')] => None
+        ]);
+        let tokens = lexer.lex("AA\n A")?;
+        assert_eq!(2, tokens.len());
+        assert_eq!(&Location::new(0, 1, 1), tokens[0].span().start());
+        assert_eq!(&Location::new(2, 1, 3), tokens[0].span().end());
+        assert_eq!(&Location::new(4, 2, 2), tokens[1].span().start());
+        assert_eq!(&Location::new(5, 2, 3), tokens[1].span().end());
+        Ok(())
+    }
+
+    #[test]
+    fn test_10() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            IF,
+            IDENTIFIER,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(con![sym![rng!('a', 'z')], ast!(sym![rng!('a', 'z')])], Some(IDENTIFIER)),
+            Rule::new(con![sym![sgl!('i')], sym![sgl!('f')]], Some(IF)).with_priority(1),
+            Rule::new(sym![sgl!(' ')], None)
+        ])?;
+        let expected = vec![
+            Token::new(IF, "if"),
+            Token::new(IDENTIFIER, "iff"),
+            Token::new(IDENTIFIER, "i"),
+        ];
+        let actual = lexer.lex("if iff i")?;
+        assert_eq!(expected, actual);
+        assert!(lexer.analyze().shadowed_rules().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_11() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            KEYWORD,
+            IDENTIFIER,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(con![sym![sgl!('i')], sym![sgl!('f')]], Some(KEYWORD)).with_location(Location::new(0, 1, 1)),
+            Rule::new(con![sym![rng!('a', 'z')], ast!(sym![rng!('a', 'z')])], Some(IDENTIFIER)).with_location(Location::new(12, 2, 1)),
+            Rule::new(sym![sgl!(' ')], None)
+        ])?;
+        let expected = vec![
+            Token::new(KEYWORD, "if"),
+            Token::new(IDENTIFIER, "iff"),
+        ];
+        let actual = lexer.lex("if iff")?;
+        assert_eq!(expected, actual);
+        assert_eq!(vec![0, 1, 2], lexer.rules().iter().map(Rule::id).collect::<Vec<_>>());
+        assert_eq!(Some(2), lexer.rules()[1].location().map(Location::line));
+        assert_eq!(None, lexer.rules()[2].location());
+        Ok(())
+    }
+
+    #[test]
+    fn test_12() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            WORD,
+            QUOTE,
+            TEXT,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(con![sym![rng!('a', 'z')], ast!(sym![rng!('a', 'z')])], Some(WORD)),
+            Rule::new(sym![sgl!('"')], Some(QUOTE)).with_next_mode("string"),
+            Rule::new(con![neg![sgl!('"')], ast!(neg![sgl!('"')])], Some(TEXT)).with_mode("string"),
+            Rule::new(sym![sgl!('"')], Some(QUOTE)).with_mode("string").with_next_mode(DEFAULT_MODE)
+        ])?;
+        let expected = vec![
+            Token::new(WORD, "ab"),
+            Token::new(QUOTE, "\""),
+            Token::new(TEXT, "c d"),
+            Token::new(QUOTE, "\""),
+            Token::new(WORD, "e"),
+        ];
+        let actual = lexer.lex("ab\"c d\"e")?;
+        assert_eq!(expected, actual);
+        assert_eq!(vec![DEFAULT_MODE, "string"], lexer.modes().collect::<Vec<_>>());
+        assert!(lexer.analyze().is_clean());
+        assert_eq!(Some("undefined mode"), Lexer::from_rules(vec![
+            Rule::new(sym![sgl!('a')], Some(WORD)).with_next_mode("missing")
+        ]).err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_13() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            IF,
+            IDENT,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(con![sym![sgl!('i')], sym![sgl!('f')]], Some(IF)),
+            Rule::new(con![sym![rng!('a', 'z')], ast!(sym![rng!('a', 'z')])], Some(IDENT)),
+            Rule::new(neg![sgl!('"')], None).with_mode("string")
+        ])?;
+        let dot = lexer.to_dot();
+        assert!(dot.starts_with("digraph dfa {\n"));
+        assert!(dot.contains("label=\"default\""));
+        assert!(dot.contains("label=\"string\""));
+        assert!(dot.contains("IF\\nIDENT\", shape=doublecircle"));
+        assert!(dot.contains("[label=\"f\"]"));
+        assert!(dot.contains("[label=\"[a-eg-z]\"]"));
+        assert!(lexer.to_dot_with(&DotOptions::new().collapse_above(1)).contains("[label=\"[^\\\"]\"]"));
+        assert!(lexer.to_dot_with(&DotOptions::new().collapse_above(0)).contains("[label=\"2 ranges\"]"));
+        let nfa = lexer.nfa_to_dot();
+        assert!(nfa.starts_with("digraph nfa {\n"));
+        assert!(nfa.contains("[label=\"ε\"]"));
+        assert_eq!(nfa, lexer.nfa_to_dot_with(&DotOptions::new()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_14() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            IF,
+            IDENT,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(con![sym![sgl!('i')], sym![sgl!('f')]], Some(IF)),
+            Rule::new(con![sym![rng!('a', 'z')], ast!(sym![rng!('a', 'z')])], Some(IDENT))
+        ])?;
+        let analysis = lexer.analyze();
+        let ambiguous_rules = analysis.ambiguous_rules().iter().map(|(winner, loser)| (*winner.token_kind(), *loser.token_kind())).collect::<Vec<_>>();
+        assert_eq!(vec![(Some(IF), Some(IDENT))], ambiguous_rules);
+        assert!(analysis.is_clean());
+        assert_eq!(4, analysis.state_count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_15() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            IF,
+            IDENT,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(con![sym![sgl!('i')], sym![sgl!('f')]], Some(IF)),
+            Rule::new(con![sym![rng!('a', 'z')], ast!(sym![rng!('a', 'z')])], Some(IDENT)),
+            Rule::new(sym![sgl!(' ')], None)
+        ])?;
+        let (steps, tokens) = lexer.trace("ifa b");
+        assert_eq!(vec![Token::new(IDENT, "ifa"), Token::new(IDENT, "b")], tokens?);
+        let expected = vec![
+            ('i', true, vec![Some(IF), Some(IDENT)]),
+            ('f', false, vec![Some(IF), Some(IDENT)]),
+            ('a', false, vec![Some(IDENT)]),
+            (' ', true, vec![None]),
+            ('b', true, vec![Some(IDENT)]),
+        ];
+        let actual = steps.iter().map(|step| (step.character(), step.starts_token(), step.alive_rules().iter().map(|rule| *rule.token_kind()).collect::<Vec<_>>())).collect::<Vec<_>>();
+        assert_eq!(expected, actual);
+        assert!(steps.iter().all(|step| step.mode() == DEFAULT_MODE));
+        assert_eq!(Location::new(4, 1, 5), *steps[4].location());
+        let (steps, tokens) = lexer.trace("a!");
+        assert_eq!(Some("partial match"), tokens.as_ref().err().map(|error| error.message()));
+        assert_eq!(1, steps.len());
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_16() -> Result<()> {
+        let token = Token::with_span(String::from("IDENT"), "x", crate::Span::new(Location::new(2, 1, 3), Location::new(3, 1, 4)));
+        let json = concat!(
+            "{\"kind\":\"IDENT\",\"text\":\"x\",\"span\":",
+            "{\"start\":{\"index\":2,\"line\":1,\"column\":3},",
+            "\"end\":{\"index\":3,\"line\":1,\"column\":4}}}"
+        );
+        assert_eq!(json, serde_json::to_string(&token).map_err(|_| "cannot serialize")?);
+        let deserialized: Token<String> = serde_json::from_str(json).map_err(|_| "cannot deserialize")?;
+        assert!(token.eq_with_span(&deserialized));
+        Ok(())
+    }
+
+    #[test]
+    fn test_17() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            IF,
+            IDENT,
+            NUMBER,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(con![sym![sgl!('i')], sym![sgl!('f')]], Some(IF)),
+            Rule::new(con![sym![rng!('a', 'z')], ast!(sym![rng!('a', 'z')])], Some(IDENT)),
+            Rule::new(con![sym![rng!('0', '9')], ast!(sym![rng!('0', '9')])], Some(NUMBER)),
+            Rule::new(ast!(sym![sgl!(' ')]), None)
+        ])?;
+        let options = SampleOptions::new().with_max_tokens(12).with_max_token_length(4);
+        for seed in 0..32 {
+            let (text, tokens) = lexer.sample(&mut SeededRng::new(seed), &options);
+            assert_eq!(lexer.lex(&text)?, tokens);
+            assert_eq!((text, tokens), lexer.sample(&mut SeededRng::new(seed), &options));
+        }
+        let options = options.with_weight(IF, 0).with_weight(IDENT, 0);
+        let (_, tokens) = lexer.sample(&mut SeededRng::new(7), &options);
+        assert!(!tokens.is_empty());
+        assert!(tokens.iter().all(|token| *token.kind() == NUMBER));
+        Ok(())
+    }
+
+    #[test]
+    fn test_18() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            IF,
+            IDENT,
+            NUMBER,
+            X,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(con![sym![sgl!('i')], sym![sgl!('f')]], Some(IF)),
+            Rule::new(con![sym![rng!('a', 'z')], ast!(sym![rng!('a', 'z')])], Some(IDENT)),
+            Rule::new(con![sym![rng!('0', '9')], ast!(sym![rng!('0', '9')])], Some(NUMBER)),
+            Rule::new(sym![sgl!('x')], Some(X)),
+            Rule::new(con![sym![sgl!(' ')], ast!(sym![sgl!(' ')])], None)
+        ])?;
+        let expected = vec![Some(String::from("if")), Some(String::from("a")), Some(String::from("0")), None, Some(String::from(" "))];
+        assert_eq!(expected, lexer.examples());
+        assert_eq!(vec![String::from("0"), String::from("00")], lexer.examples_up_to(2)[2]);
+        for (rule, example) in lexer.rules().iter().zip(lexer.examples()) {
+            if let (Some(token_kind), Some(example)) = (rule.token_kind(), example) {
+                assert_eq!(vec![Token::new(*token_kind, &example)], lexer.lex(&example)?);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_19() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            X,
+            Y,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::new(map![
+            sym![sgl!('a')] => Some(X),
+            alt![sym![sgl!('a')], sym![sgl!('b')]] => Some(Y)
+        ]);
+        let error = lexer.lex("a").err().ok_or("no error")?;
+        assert_eq!("inconsistent tokens in final state", error.message());
+        assert_eq!(vec![Token::new(Y, "b")], lexer.lex("b")?);
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(sym![sgl!('a')], Some(X)),
+            Rule::new(alt![sym![sgl!('a')], sym![sgl!('b')]], Some(Y))
+        ])?;
+        assert_eq!(vec![Token::new(X, "a")], lexer.lex("a")?);
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_20() -> Result<()> {
+        let lexer = Lexer::from_rules(vec![Rule::new(sym![sgl!('a')], Some(String::from("A")))])?;
+        let error = lexer.lex("ab").err().ok_or("no error")?;
+        let json = serde_json::to_string(&error)?;
+        assert_eq!(error, serde_json::from_str::<crate::Error>(&json)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_21() -> Result<()> {
+        #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+        enum TokenKind {
+            X,
+        }
+        use TokenKind::*;
+        let lexer = Lexer::from_rules(vec![
+            Rule::new(con![sym![sgl!('a')], sym![sgl!('c')]], Some(X)).with_name("AC"),
+            Rule::new(con![sym![sgl!('b')], sym![sgl!('c')]], Some(X)).with_name("BC")
+        ])?;
+        assert_eq!(3, live_states(&lexer.modes[DEFAULT_MODE].dfa).len());
+        let alive_rules = |text| -> Vec<Vec<String>> {
+            lexer.trace(text).0.iter().map(|step| step.alive_rules().iter().map(Rule::to_string).collect()).collect()
+        };
+        assert_eq!(vec![vec!["AC"], vec!["AC"]], alive_rules("ac"));
+        assert_eq!(vec![vec!["BC"], vec!["BC"]], alive_rules("bc"));
+        let dot = lexer.to_dot();
+        assert!(dot.contains("\\nAC | BC\", shape=doublecircle"));
+        assert!(!dot.contains("\\nAC\\nBC"));
+        Ok(())
+    }
 }
@@ -5,10 +5,40 @@ use regular_expression_bootstrap::StateGenerator;
 #[macro_use]
 mod util;
 mod lexer;
-
-pub use crate::lexer::{
-    Token,
-    Lexer
+mod minimize;
+mod analysis;
+mod segments;
+mod span;
+mod rule;
+mod mode;
+mod dot;
+mod trace;
+mod sample;
+mod examples;
+mod error;
+
+pub use crate::{
+    lexer::{
+        Token,
+        Lexer
+    },
+    analysis::{
+        Analysis,
+        ShadowedRule,
+    },
+    span::{
+        Location,
+        Span,
+    },
+    rule::Rule,
+    mode::DEFAULT_MODE,
+    dot::DotOptions,
+    trace::Step,
+    sample::{
+        SampleOptions,
+        SeededRng,
+    },
+    error::Error,
 };
 
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
@@ -23,10 +53,6 @@ impl<T: Clone> TokenState<T> {
         TokenState { uuid: Uuid::new_v4().as_u128(), sequence_number: 0, token_kind }
     }
 
-    fn token_kind(&self) -> &Option<T> {
-        &self.token_kind
-    }
-
     fn clear_token_kind(&mut self) {
         self.token_kind = None;
     }
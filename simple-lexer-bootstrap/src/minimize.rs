@@ -0,0 +1,148 @@
+use std::collections::{
+    BTreeSet as Set,
+    BTreeMap as Map,
+    VecDeque,
+};
+use segment_map::Segment;
+use finite_automata::{
+    Dfa,
+    StateIndex,
+};
+use crate::{
+    rule::{
+        Rule,
+        resolve,
+    },
+    TokenState,
+};
+
+// The minimal DFA, the rules each of its final states accepts, and the states
+// merged into each of its states.
+type Minimized<T> = (Dfa<Set<TokenState<T>>, u32>, Map<StateIndex, Set<usize>>, Map<StateIndex, Set<StateIndex>>);
+
+// Partition refinement over the live states of the DFA. The initial partition
+// separates states by finality and by what the winning rule does at the end
+// of a token, producing its token kind and switching to its next mode, or by
+// the winning rules being in conflict, so states won by different rules still
+// merge when lexing cannot tell them apart. Also returns the rules each state
+// of the minimal DFA accepts, which for a merged state are those of every
+// state merged into it. Those resolve the same way as each merged state, but
+// a merged state does not accept all of them, so the states merged into each
+// state of the minimal DFA are returned as well.
+pub(crate) fn minimize<T: Clone + Ord>(dfa: &Dfa<Set<TokenState<T>>, u32>, rules: &[Rule<T>], rule_indices: &Map<TokenState<T>, usize>, ordered: bool) -> Minimized<T> {
+    let states = live_states(dfa);
+    let mut blocks = Map::new();
+    let mut keys = Map::new();
+    for &state_index in &states {
+        let accepted = accepting_rules(dfa.states_index(state_index), rule_indices);
+        let winner = resolve(rules, &accepted, ordered)
+            .map(|winner| winner.filter(|_| dfa.is_final(state_index)).map(|winner| (rules[winner].token_kind().clone(), rules[winner].next_mode().map(String::from))));
+        let key = (dfa.is_final(state_index), winner);
+        let next_block = keys.len();
+        blocks.insert(state_index, *keys.entry(key).or_insert(next_block));
+    }
+    let mut blocks_len = keys.len();
+    loop {
+        let mut signatures = Map::new();
+        let mut refined_blocks = Map::new();
+        for &state_index in &states {
+            let signature = (blocks[&state_index], outgoing(dfa, state_index, &blocks));
+            let next_block = signatures.len();
+            refined_blocks.insert(state_index, *signatures.entry(signature).or_insert(next_block));
+        }
+        blocks = refined_blocks;
+        if signatures.len() == blocks_len {
+            break;
+        }
+        blocks_len = signatures.len();
+    }
+    // each block is represented by its lowest state index
+    let mut representatives = Map::new();
+    let mut block_rules: Map<usize, Set<usize>> = Map::new();
+    for (&state_index, &block) in &blocks {
+        representatives.entry(block).or_insert(state_index);
+        if dfa.is_final(state_index) {
+            block_rules.entry(block).or_default().extend(accepting_rules(dfa.states_index(state_index), rule_indices));
+        }
+    }
+    let initial_block = blocks[&dfa.initial_index()];
+    let mut minimal = Dfa::new(dfa.states_index(dfa.initial_index()).clone());
+    let mut minimal_indices = Map::new();
+    minimal_indices.insert(initial_block, minimal.initial_index());
+    for (&block, &state_index) in &representatives {
+        if block != initial_block {
+            minimal_indices.insert(block, minimal.states_insert(dfa.states_index(state_index).clone()));
+        }
+    }
+    for (&block, &state_index) in &representatives {
+        for (segment, target_block) in outgoing(dfa, state_index, &blocks) {
+            minimal.transitions_insert((minimal_indices[&block], segment, minimal_indices[&target_block]));
+        }
+        if dfa.is_final(state_index) {
+            minimal.set_final(minimal_indices[&block]);
+        }
+    }
+    let accepting = block_rules.into_iter()
+        .filter(|(_, rule_indices)| !rule_indices.is_empty())
+        .map(|(block, rule_indices)| (minimal_indices[&block], rule_indices))
+        .collect();
+    let mut merged: Map<StateIndex, Set<StateIndex>> = Map::new();
+    for (&state_index, block) in &blocks {
+        merged.entry(minimal_indices[block]).or_default().insert(state_index);
+    }
+    (minimal, accepting, merged)
+}
+
+// States reachable from the initial state from which a final state is also
+// reachable. The initial state is always kept.
+pub(crate) fn live_states<T: Clone + Ord>(dfa: &Dfa<Set<TokenState<T>>, u32>) -> Set<StateIndex> {
+    let mut reachable = Set::new();
+    let mut predecessors: Map<StateIndex, Set<StateIndex>> = Map::new();
+    let mut queue = VecDeque::new();
+    reachable.insert(dfa.initial_index());
+    queue.push_back(dfa.initial_index());
+    while let Some(source_index) = queue.pop_front() {
+        for transition_index in dfa.transition_indices_from(source_index) {
+            let (_, _, target_index) = dfa.transitions_index(transition_index);
+            predecessors.entry(target_index).or_default().insert(source_index);
+            if reachable.insert(target_index) {
+                queue.push_back(target_index);
+            }
+        }
+    }
+    let mut live = Set::new();
+    for &state_index in &reachable {
+        if dfa.is_final(state_index) {
+            live.insert(state_index);
+            queue.push_back(state_index);
+        }
+    }
+    while let Some(target_index) = queue.pop_front() {
+        if let Some(source_indices) = predecessors.get(&target_index) {
+            for &source_index in source_indices {
+                if live.insert(source_index) {
+                    queue.push_back(source_index);
+                }
+            }
+        }
+    }
+    live.insert(dfa.initial_index());
+    live
+}
+
+// Indices of the rules whose final states are among the token states.
+pub(crate) fn accepting_rules<T: Clone + Ord>(token_states: &Set<TokenState<T>>, rule_indices: &Map<TokenState<T>, usize>) -> Set<usize> {
+    token_states.iter().filter_map(|token_state| rule_indices.get(token_state).cloned()).collect()
+}
+
+// Transitions into states outside the partition (dead or unreachable) are dropped.
+fn outgoing<T: Clone + Ord>(dfa: &Dfa<Set<TokenState<T>>, u32>, state_index: StateIndex, blocks: &Map<StateIndex, usize>) -> Set<(Segment<u32>, usize)> {
+    let mut outgoing = Set::new();
+    for transition_index in dfa.transition_indices_from(state_index) {
+        let (_, segment, target_index) = dfa.transitions_index(transition_index);
+        if let Some(&target_block) = blocks.get(&target_index) {
+            outgoing.insert((*segment, target_block));
+        }
+    }
+    outgoing
+}
@@ -0,0 +1,134 @@
+use std::collections::{
+    BTreeSet as Set,
+    BTreeMap as Map,
+};
+use segment_map::Segment;
+use finite_automata::{
+    Enfa,
+    Dfa,
+    StateIndex,
+    Subsume,
+    states_contains_from,
+};
+use crate::{
+    minimize::{
+        minimize,
+        live_states,
+        accepting_rules,
+    },
+    rule::{
+        Rule,
+        resolve,
+    },
+    TokenState,
+    TokenStateGenerator,
+};
+
+// The mode every lexer starts in, and the mode of rules not given one.
+pub const DEFAULT_MODE: &str = "default";
+
+// The automaton matching the rules of a single mode. Rule indices index the
+// rules of the whole lexer.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Mode<T> {
+    pub(crate) rule_indices: Vec<usize>,
+    pub(crate) dfa: Dfa<Set<TokenState<T>>, u32>,
+    pub(crate) accepting: Map<StateIndex, Set<usize>>,
+    // the DFA before minimization, the rules each of its final states
+    // accepts, and the states of it merged into each state of the minimal
+    // DFA, since a merged state only accepts some of the rules accepting
+    // lists for it
+    pub(crate) unminimized: Dfa<Set<TokenState<T>>, u32>,
+    pub(crate) unminimized_accepting: Map<StateIndex, Set<usize>>,
+    pub(crate) merged: Map<StateIndex, Set<StateIndex>>,
+    // the rules some final state reachable from each state of the DFA before
+    // minimization accepts
+    pub(crate) alive: Map<StateIndex, Set<usize>>,
+    // the live states of the DFA numbered in order, so that traces and DOT
+    // exports refer to states the same way
+    pub(crate) numbers: Map<StateIndex, usize>,
+    // the rules accepted together by each final state of the DFA before
+    // minimization, other than its initial state, and the rules that initial
+    // state accepts, which are exact where merged states are not
+    pub(crate) acceptances: Set<Set<usize>>,
+    pub(crate) nullable: Set<usize>,
+    // whether ties in priority are broken by production order
+    pub(crate) ordered: bool,
+}
+
+impl<T: Clone + Ord> Mode<T> {
+    pub(crate) fn new(rules: &[Rule<T>], mode_rule_indices: Vec<usize>, ordered: bool) -> Mode<T> {
+        let (enfa, rule_indices) = nfa(rules, &mode_rule_indices);
+        let unminimized = Dfa::from(&enfa);
+        let mut acceptances = Set::new();
+        let mut nullable = Set::new();
+        let mut unminimized_accepting = Map::new();
+        let states = live_states(&unminimized);
+        for &state_index in &states {
+            if unminimized.is_final(state_index) {
+                let state_rule_indices = accepting_rules(unminimized.states_index(state_index), &rule_indices);
+                if state_rule_indices.is_empty() {
+                    continue;
+                }
+                unminimized_accepting.insert(state_index, state_rule_indices.clone());
+                if state_index == unminimized.initial_index() {
+                    nullable = state_rule_indices;
+                } else {
+                    acceptances.insert(state_rule_indices);
+                }
+            }
+        }
+        let (dfa, accepting, merged) = minimize(&unminimized, rules, &rule_indices, ordered);
+        let mut alive: Map<StateIndex, Set<usize>> = states.iter().map(|&state_index| (state_index, unminimized_accepting.get(&state_index).cloned().unwrap_or_default())).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &state_index in &states {
+                for transition_index in unminimized.transition_indices_from(state_index) {
+                    let (_, _, target_index) = unminimized.transitions_index(transition_index);
+                    let target_rule_indices = alive.get(&target_index).cloned().unwrap_or_default();
+                    let rule_indices = alive.entry(state_index).or_default();
+                    let count = rule_indices.len();
+                    rule_indices.extend(target_rule_indices);
+                    changed |= rule_indices.len() != count;
+                }
+            }
+        }
+        let numbers = live_states(&dfa).into_iter().enumerate().map(|(number, state_index)| (state_index, number)).collect();
+        Mode { rule_indices: mode_rule_indices, dfa, accepting, unminimized, unminimized_accepting, merged, alive, numbers, acceptances, nullable, ordered }
+    }
+
+    // The rule which takes precedence in a state, if it is final.
+    pub(crate) fn winner(&self, rules: &[Rule<T>], state_index: StateIndex) -> Result<Option<usize>, &'static str> {
+        match self.accepting.get(&state_index) {
+            Some(rule_indices) => resolve(rules, rule_indices, self.ordered),
+            None => Ok(None),
+        }
+    }
+}
+
+// The alternation of the given rules, along with the rule each final state
+// belongs to.
+pub(crate) fn nfa<T: Clone + Ord>(rules: &[Rule<T>], mode_rule_indices: &[usize]) -> (Enfa<TokenState<T>, u32>, Map<TokenState<T>, usize>) {
+    let mut fas = Vec::new();
+    let mut rule_indices = Map::new();
+    for &rule_index in mode_rule_indices {
+        let rule = &rules[rule_index];
+        let fa = rule.expression().as_enfa(&mut TokenStateGenerator::new(rule.token_kind().clone()));
+        for fa_final_index in fa.final_indices() {
+            rule_indices.insert(fa.states_index(fa_final_index).clone(), rule_index);
+        }
+        fas.push(fa);
+    }
+    let mut alt = Enfa::new(TokenState::new(None));
+    for fa in fas {
+        alt.subsume(&fa);
+        let fa_initial_index = states_contains_from(&alt, &fa, fa.initial_index()).expect("state does not exist");
+        alt.transitions_insert((alt.initial_index(), Segment::empty(), fa_initial_index));
+        for fa_final_index in fa.final_indices() {
+            let fa_final_index = states_contains_from(&alt, &fa, fa_final_index).expect("state does not exist");
+            alt.set_final(fa_final_index);
+        }
+    }
+    (alt, rule_indices)
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::BTreeSet as Set,
+    fmt,
+};
+use regular_expression_bootstrap::Expression;
+use crate::{
+    span::Location,
+    mode::DEFAULT_MODE,
+};
+
+// A single production of a lexer. Rules without a token kind consume their
+// matches without producing tokens. When several rules match the same text,
+// the rule with the highest priority wins, and among those the earliest rule.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Rule<T> {
+    id: usize,
+    expression: Expression,
+    token_kind: Option<T>,
+    priority: i64,
+    name: Option<String>,
+    location: Option<Location>,
+    mode: String,
+    next_mode: Option<String>,
+}
+
+impl<T> Rule<T> {
+    pub fn new(expression: Expression, token_kind: Option<T>) -> Rule<T> {
+        Rule { id: 0, expression, token_kind, priority: 0, name: None, location: None, mode: String::from(DEFAULT_MODE), next_mode: None }
+    }
+
+    pub fn with_priority(mut self, priority: i64) -> Rule<T> {
+        self.priority = priority;
+        self
+    }
+
+    // The name used for the rule in diagnostics.
+    pub fn with_name(mut self, name: &str) -> Rule<T> {
+        self.name = Some(String::from(name));
+        self
+    }
+
+    // Where the rule was written, such as its line in a spec.
+    pub fn with_location(mut self, location: Location) -> Rule<T> {
+        self.location = Some(location);
+        self
+    }
+
+    // The mode the rule is active in.
+    pub fn with_mode(mut self, mode: &str) -> Rule<T> {
+        self.mode = String::from(mode);
+        self
+    }
+
+    // The mode the lexer switches to after the rule matches.
+    pub fn with_next_mode(mut self, next_mode: &str) -> Rule<T> {
+        self.next_mode = Some(String::from(next_mode));
+        self
+    }
+
+    pub(crate) fn with_id(mut self, id: usize) -> Rule<T> {
+        self.id = id;
+        self
+    }
+
+    // The position of the rule in the production list of its lexer.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+
+    pub fn token_kind(&self) -> &Option<T> {
+        &self.token_kind
+    }
+
+    pub fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref()
+    }
+
+    pub fn mode(&self) -> &str {
+        &self.mode
+    }
+
+    pub fn next_mode(&self) -> Option<&str> {
+        self.next_mode.as_deref()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Display for Rule<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.name, &self.token_kind) {
+            (Some(name), _) => write!(f, "{}", name),
+            (None, Some(token_kind)) => write!(f, "{:?}", token_kind),
+            (None, None) => write!(f, "skipped {:?}", self.expression),
+        }
+    }
+}
+
+// The rule which takes precedence among the candidates, or None if there are
+// none. Ordered rules break ties in priority by production order. Unordered
+// rules, such as those built from a map of productions, have no order their
+// caller chose, so the rules tied on priority must agree on their token kind,
+// and a rule producing a token takes precedence over a skipped one.
+pub(crate) fn resolve<T: PartialEq>(rules: &[Rule<T>], rule_indices: &Set<usize>, ordered: bool) -> Result<Option<usize>, &'static str> {
+    let winners = winning_rules(rules, rule_indices);
+    if ordered {
+        return Ok(winners.first().cloned());
+    }
+    let producing = winners.iter().cloned().filter(|&rule_index| rules[rule_index].token_kind.is_some()).collect::<Vec<_>>();
+    if producing.iter().any(|&rule_index| rules[rule_index].token_kind != rules[producing[0]].token_kind) {
+        return Err("inconsistent tokens in final state");
+    }
+    Ok(producing.first().or_else(|| winners.first()).cloned())
+}
+
+// The candidate rules with the highest priority, in production order.
+pub(crate) fn winning_rules<T>(rules: &[Rule<T>], rule_indices: &Set<usize>) -> Vec<usize> {
+    let priority = rule_indices.iter().map(|&rule_index| rules[rule_index].priority).max();
+    rule_indices.iter().cloned().filter(|&rule_index| Some(rules[rule_index].priority) == priority).collect()
+}
@@ -0,0 +1,178 @@
+use std::collections::{
+    BTreeSet as Set,
+    BTreeMap as Map,
+    VecDeque,
+    btree_map::Entry,
+};
+use finite_automata::StateIndex;
+use crate::{
+    minimize::live_states,
+    mode::Mode,
+    rule::Rule,
+    segments::{
+        bounds,
+        character,
+    },
+};
+
+// A small seedable random number generator (SplitMix64), so samples can be
+// reproduced from their seed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // A number below the bound, which must not be zero.
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+// Options for Lexer::sample. Rules are picked in proportion to the weight of
+// their token kind, which is one unless given, and rules without a token kind
+// always have weight one. A weight of zero never picks the kind.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SampleOptions<T> {
+    max_tokens: usize,
+    max_token_length: usize,
+    weights: Map<T, u64>,
+}
+
+impl<T: Ord> SampleOptions<T> {
+    pub fn new() -> SampleOptions<T> {
+        SampleOptions { max_tokens: 16, max_token_length: 8, weights: Map::new() }
+    }
+
+    // The number of rules matched, including those producing no token. The
+    // sample can be shorter when no rule fits after the text so far.
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> SampleOptions<T> {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    // The number of characters in the text of a single token.
+    pub fn with_max_token_length(mut self, max_token_length: usize) -> SampleOptions<T> {
+        self.max_token_length = max_token_length;
+        self
+    }
+
+    pub fn with_weight(mut self, token_kind: T, weight: u64) -> SampleOptions<T> {
+        self.weights.insert(token_kind, weight);
+        self
+    }
+
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    pub fn max_token_length(&self) -> usize {
+        self.max_token_length
+    }
+
+    pub(crate) fn weight(&self, rule: &Rule<T>) -> u64 {
+        rule.token_kind().as_ref().and_then(|token_kind| self.weights.get(token_kind)).cloned().unwrap_or(1)
+    }
+}
+
+impl<T: Ord> Default for SampleOptions<T> {
+    fn default() -> SampleOptions<T> {
+        SampleOptions::new()
+    }
+}
+
+// Picks one of the rules in proportion to its weight.
+pub(crate) fn pick_rule<T: Ord>(rules: &[Rule<T>], rule_indices: &[usize], options: &SampleOptions<T>, rng: &mut SeededRng) -> Option<usize> {
+    let total: u64 = rule_indices.iter().map(|&rule_index| options.weight(&rules[rule_index])).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut target = rng.below(total);
+    for &rule_index in rule_indices {
+        let weight = options.weight(&rules[rule_index]);
+        if target < weight {
+            return Some(rule_index);
+        }
+        target -= weight;
+    }
+    None
+}
+
+// The distance from each state of the DFA of a mode to the nearest state the
+// rule wins, other than the initial state, found backwards. States from which
+// the rule cannot win are left out.
+pub(crate) fn distances<T: Clone + Ord>(rules: &[Rule<T>], mode: &Mode<T>, rule_index: usize) -> Map<StateIndex, usize> {
+    let initial_index = mode.dfa.initial_index();
+    let states = live_states(&mode.dfa);
+    let wins = |state_index: StateIndex| state_index != initial_index && mode.winner(rules, state_index) == Ok(Some(rule_index));
+    let mut predecessors: Map<StateIndex, Set<StateIndex>> = Map::new();
+    for &state_index in &states {
+        for transition_index in mode.dfa.transition_indices_from(state_index) {
+            let (_, _, target_index) = mode.dfa.transitions_index(transition_index);
+            predecessors.entry(target_index).or_default().insert(state_index);
+        }
+    }
+    let mut distances = Map::new();
+    let mut queue = VecDeque::new();
+    for &state_index in states.iter().filter(|&&state_index| wins(state_index)) {
+        distances.insert(state_index, 0);
+        queue.push_back(state_index);
+    }
+    while let Some(state_index) = queue.pop_front() {
+        let distance = distances[&state_index] + 1;
+        for &predecessor_index in predecessors.get(&state_index).into_iter().flatten() {
+            if let Entry::Vacant(entry) = distances.entry(predecessor_index) {
+                entry.insert(distance);
+                queue.push_back(predecessor_index);
+            }
+        }
+    }
+    distances
+}
+
+// A random walk through the DFA of a mode which ends in a state the rule
+// wins, taking at least one and at most max_length characters, given the
+// rule's distances. At each step the walk either stops, if it may, or takes
+// a transition from which a state the rule wins is still within reach. None
+// if no such walk exists.
+pub(crate) fn walk<T: Clone + Ord>(mode: &Mode<T>, distances: &Map<StateIndex, usize>, max_length: usize, rng: &mut SeededRng) -> Option<String> {
+    let wins = |state_index: StateIndex| distances.get(&state_index) == Some(&0);
+    let mut text = String::new();
+    let mut state_index = mode.dfa.initial_index();
+    for remaining in (1..=max_length).rev() {
+        let mut choices = Vec::new();
+        for transition_index in mode.dfa.transition_indices_from(state_index) {
+            let (_, segment, target_index) = mode.dfa.transitions_index(transition_index);
+            if distances.get(&target_index).is_some_and(|&distance| distance < remaining) {
+                choices.extend(bounds(segment).map(|range| (range, target_index)));
+            }
+        }
+        let may_stop = !text.is_empty() && wins(state_index);
+        let choice_count = choices.len() as u64 + if may_stop { 1 } else { 0 };
+        if choice_count == 0 {
+            break;
+        }
+        let choice = rng.below(choice_count) as usize;
+        if choice == choices.len() {
+            return Some(text);
+        }
+        let ((lower, upper), target_index) = choices[choice];
+        // readable three times in four, so other characters still turn up
+        let readable = rng.below(4) != 0;
+        text.push(character(lower, upper, readable, |count| rng.below(count))?);
+        state_index = target_index;
+    }
+    if !text.is_empty() && wins(state_index) { Some(text) } else { None }
+}
@@ -0,0 +1,78 @@
+use std::cmp::max;
+use segment_map::Segment;
+
+const MAX_SCALAR_VALUE: u32 = 0x10FFFF;
+const SURROGATES: (u32, u32) = (0xD800, 0xDFFF);
+const VISIBLE_ASCII: (u32, u32) = (0x21, 0x7E);
+
+// The inclusive bounds of a segment, or None if the segment is empty.
+// Segments include their lower bound but not their upper bound.
+pub(crate) fn bounds(segment: &Segment<u32>) -> Option<(u32, u32)> {
+    if segment.lower() < segment.upper() { Some((*segment.lower(), segment.upper() - 1)) } else { None }
+}
+
+// A character of the inclusive bounds, picked by index from the number of
+// candidates. With readable set, the candidates are narrowed to visible ASCII
+// where the bounds overlap it, so strings read well in messages. Surrogates
+// are never candidates since they cannot appear in a str.
+pub(crate) fn character<F: FnOnce(u64) -> u64>(lower: u32, upper: u32, readable: bool, pick: F) -> Option<char> {
+    let upper = upper.min(MAX_SCALAR_VALUE);
+    let (visible_lower, visible_upper) = VISIBLE_ASCII;
+    let (lower, upper) = if readable && lower <= visible_upper && upper >= visible_lower {
+        (lower.max(visible_lower), upper.min(visible_upper))
+    } else {
+        (lower, upper)
+    };
+    if lower > upper {
+        return None;
+    }
+    // the candidates below the surrogates, then those above
+    let (surrogates_lower, surrogates_upper) = SURROGATES;
+    let below = if lower < surrogates_lower { u64::from(upper.min(surrogates_lower - 1) - lower) + 1 } else { 0 };
+    let above_lower = lower.max(surrogates_upper + 1);
+    let above = if upper >= above_lower { u64::from(upper - above_lower) + 1 } else { 0 };
+    if below + above == 0 {
+        return None;
+    }
+    let index = pick(below + above);
+    let value = if index < below { lower + index as u32 } else { above_lower + (index - below) as u32 };
+    std::char::from_u32(value)
+}
+
+// The ranges of Unicode scalar values not covered by any of the inclusive
+// bounds. Surrogates are never reported since they cannot appear in a str.
+pub(crate) fn complement(mut covered: Vec<(u32, u32)>) -> Vec<(char, char)> {
+    covered.sort();
+    let mut uncovered = Vec::new();
+    let mut next = 0u64;
+    for (lower, upper) in covered {
+        if u64::from(lower) > next {
+            push_scalar_values(&mut uncovered, next as u32, lower - 1);
+        }
+        next = max(next, u64::from(upper) + 1);
+    }
+    if next <= u64::from(MAX_SCALAR_VALUE) {
+        push_scalar_values(&mut uncovered, next as u32, MAX_SCALAR_VALUE);
+    }
+    uncovered
+}
+
+fn push_scalar_values(ranges: &mut Vec<(char, char)>, lower: u32, upper: u32) {
+    let upper = upper.min(MAX_SCALAR_VALUE);
+    if lower > upper {
+        return;
+    }
+    let (surrogates_lower, surrogates_upper) = SURROGATES;
+    if lower < surrogates_lower {
+        push_range(ranges, lower, upper.min(surrogates_lower - 1));
+    }
+    if upper > surrogates_upper {
+        push_range(ranges, lower.max(surrogates_upper + 1), upper);
+    }
+}
+
+fn push_range(ranges: &mut Vec<(char, char)>, lower: u32, upper: u32) {
+    let lower = std::char::from_u32(lower).expect("not scalar value");
+    let upper = std::char::from_u32(upper).expect("not scalar value");
+    ranges.push((lower, upper));
+}
@@ -0,0 +1,67 @@
+// A position in the lexed text. The index is a byte offset, while lines and
+// columns count characters starting from one. With the serde feature, a
+// location serializes as `{"index": 0, "line": 1, "column": 1}`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location {
+    index: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Location {
+    pub fn new(index: usize, line: usize, column: usize) -> Location {
+        Location { index, line, column }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub(crate) fn advance(&mut self, character: char) {
+        self.index += character.len_utf8();
+        if character == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+impl Default for Location {
+    fn default() -> Location {
+        Location { index: 0, line: 1, column: 1 }
+    }
+}
+
+// The half-open range of text from the start location up to the end location.
+// With the serde feature, a span serializes as `{"start": ..., "end": ...}`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    start: Location,
+    end: Location,
+}
+
+impl Span {
+    pub fn new(start: Location, end: Location) -> Span {
+        Span { start, end }
+    }
+
+    pub fn start(&self) -> &Location {
+        &self.start
+    }
+
+    pub fn end(&self) -> &Location {
+        &self.end
+    }
+}
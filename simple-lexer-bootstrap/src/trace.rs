@@ -0,0 +1,55 @@
+use crate::{
+    rule::Rule,
+    span::Location,
+};
+
+// A single transition taken by the lexer, as recorded by Lexer::trace.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Step<T> {
+    mode: String,
+    character: char,
+    location: Location,
+    source_index: usize,
+    target_index: usize,
+    starts_token: bool,
+    alive_rules: Vec<Rule<T>>,
+}
+
+impl<T> Step<T> {
+    pub(crate) fn new(mode: &str, character: char, location: Location, source_index: usize, target_index: usize, starts_token: bool, alive_rules: Vec<Rule<T>>) -> Step<T> {
+        Step { mode: String::from(mode), character, location, source_index, target_index, starts_token, alive_rules }
+    }
+
+    // The mode whose DFA took the transition.
+    pub fn mode(&self) -> &str {
+        &self.mode
+    }
+
+    pub fn character(&self) -> char {
+        self.character
+    }
+
+    // Where the character is in the lexed text.
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    pub fn source_index(&self) -> usize {
+        self.source_index
+    }
+
+    pub fn target_index(&self) -> usize {
+        self.target_index
+    }
+
+    // Whether the character is the first of a token.
+    pub fn starts_token(&self) -> bool {
+        self.starts_token
+    }
+
+    // The rules which can still match once the character is consumed, in
+    // production order.
+    pub fn alive_rules(&self) -> &[Rule<T>] {
+        &self.alive_rules
+    }
+}
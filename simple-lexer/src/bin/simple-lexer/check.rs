@@ -0,0 +1,84 @@
+use std::{
+    fs,
+    path::Path,
+};
+use simple_lexer::Lexer;
+use crate::{
+    diagnostic,
+    rule_name,
+    usage_error,
+    USAGE,
+    SUCCESS,
+    FAILURE,
+};
+
+// Reports everything found about each spec without lexing any input: errors
+// building the lexer, such as syntax errors and invalid regular expressions,
+// warnings from the spec, shadowed and empty matching rules, and, as notes,
+// rules resolved by production order and the size of the DFA. Any error or
+// warning fails the check.
+pub fn check(arguments: &[String]) -> i32 {
+    let mut paths = Vec::new();
+    for argument in arguments {
+        match argument.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return SUCCESS;
+            },
+            _ if argument.starts_with('-') => return usage_error(&format!("unknown option `{}`", argument)),
+            _ => paths.push(argument.as_str()),
+        }
+    }
+    if paths.is_empty() {
+        return usage_error("missing spec");
+    }
+    let mut status = SUCCESS;
+    for path in paths {
+        let (errors, warnings) = check_spec(Path::new(path));
+        println!("{}: {} errors, {} warnings", path, errors, warnings);
+        if errors + warnings > 0 {
+            status = FAILURE;
+        }
+    }
+    status
+}
+
+// Prints the findings for a spec, returning the number of errors and
+// warnings.
+fn check_spec(path: &Path) -> (usize, usize) {
+    let source = fs::read_to_string(path).unwrap_or_default();
+    let lexer = match Lexer::<String>::from_path(path) {
+        Ok(lexer) => lexer,
+        Err(error) => {
+            eprint!("{}", diagnostic::render(&error, Some(path), &source));
+            return (error.errors().count(), 0);
+        },
+    };
+    let mut warnings = 0;
+    for warning in lexer.warnings() {
+        eprint!("{}", diagnostic::render_warning(warning, Some(path), &source));
+        warnings += 1;
+    }
+    let analysis = lexer.analyze();
+    for shadowed_rule in analysis.shadowed_rules() {
+        let rule = shadowed_rule.rule();
+        if shadowed_rule.shadowed_by().is_empty() {
+            eprint!("{}", diagnostic::render_at("warning", &format!("{} matches nothing", rule_name(rule)), Some(path), rule.location(), &source));
+        } else {
+            eprint!("{}", diagnostic::render_at("warning", &format!("{} is shadowed", rule_name(rule)), Some(path), rule.location(), &source));
+            for winner in shadowed_rule.shadowed_by() {
+                eprint!("{}", diagnostic::render_at("note", &format!("{} takes precedence", rule_name(winner)), Some(path), winner.location(), &source));
+            }
+        }
+        warnings += 1;
+    }
+    for rule in analysis.nullable_rules() {
+        eprint!("{}", diagnostic::render_at("warning", &format!("{} matches the empty string", rule_name(rule)), Some(path), rule.location(), &source));
+        warnings += 1;
+    }
+    for (winner, loser) in analysis.ambiguous_rules() {
+        eprint!("{}", diagnostic::render_at("note", &format!("{} and {} match the same text, {} wins by order", rule_name(winner), rule_name(loser), rule_name(winner)), Some(path), loser.location(), &source));
+    }
+    eprintln!("note: {}: {} states and {} transitions", path.display(), analysis.state_count(), analysis.transition_count());
+    (0, warnings)
+}
@@ -0,0 +1,87 @@
+use std::{
+    fs,
+    path::Path,
+};
+use simple_lexer::{
+    Error,
+    Location,
+};
+
+// Renders every error folded into the given one. Each error gets a header
+// line, followed by the line of source it points at with the offending text
+// underlined. Errors without a path of their own are in the given source.
+pub fn render(error: &Error, path: Option<&Path>, source: &str) -> String {
+    let mut rendered = String::new();
+    for error in error.errors() {
+        rendered.push_str(&render_one("error", error, path, source));
+    }
+    rendered
+}
+
+pub fn render_warning(warning: &Error, path: Option<&Path>, source: &str) -> String {
+    render_one("warning", warning, path, source)
+}
+
+// Renders a finding about the source which is not an Error, such as one from
+// static analysis, with a single character underlined at its location.
+pub fn render_at(severity: &str, message: &str, path: Option<&Path>, location: Option<&Location>, source: &str) -> String {
+    let mut rendered = header(severity, path, location, message);
+    if let Some(location) = location {
+        rendered.push_str(&snippet(source, location, 1));
+    }
+    rendered
+}
+
+fn render_one(severity: &str, error: &Error, path: Option<&Path>, source: &str) -> String {
+    let error_path = error.path().or(path);
+    let mut message = String::from(error.message());
+    if let Some(found) = error.text() {
+        message.push_str(&format!(", found `{}`", found.escape_debug()));
+    }
+    let mut rendered = header(severity, error_path, error.span().map(|span| span.start()), &message);
+    // errors in other files, such as included specs, are read back from disk
+    let error_source = match error.path() {
+        Some(error_path) if Some(error_path) != path => fs::read_to_string(error_path).ok(),
+        _ => Some(String::from(source)),
+    };
+    if let (Some(span), Some(error_source)) = (error.span(), error_source) {
+        let width = if span.end().line() == span.start().line() {
+            span.end().column().saturating_sub(span.start().column())
+        } else {
+            usize::MAX
+        };
+        rendered.push_str(&snippet(&error_source, span.start(), width));
+    }
+    if let Some(note) = error.note() {
+        rendered.push_str(&render_one("note", note, path, source));
+    }
+    rendered
+}
+
+fn header(severity: &str, path: Option<&Path>, location: Option<&Location>, message: &str) -> String {
+    let mut header = format!("{}: ", severity);
+    if let Some(path) = path {
+        header.push_str(&format!("{}:", path.display()));
+    }
+    if let Some(location) = location {
+        header.push_str(&format!("{}:{}:", location.line(), location.column()));
+    }
+    if path.is_some() || location.is_some() {
+        header.push(' ');
+    }
+    header.push_str(message);
+    header.push('\n');
+    header
+}
+
+// The line of source at the location, with the given number of characters
+// underlined, up to the end of the line.
+fn snippet(source: &str, location: &Location, width: usize) -> String {
+    let line = match source.lines().nth(location.line() - 1) {
+        Some(line) => line,
+        None => return String::new(),
+    };
+    let width = width.min(line.chars().count().saturating_sub(location.column() - 1)).max(1);
+    let number = location.line().to_string();
+    format!("{} | {}\n{} | {}{}\n", number, line, " ".repeat(number.len()), " ".repeat(location.column() - 1), "^".repeat(width))
+}
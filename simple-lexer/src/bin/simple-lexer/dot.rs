@@ -0,0 +1,36 @@
+use crate::{
+    load,
+    usage_error,
+    USAGE,
+    SUCCESS,
+    FAILURE,
+};
+
+// Prints the minimized DFA of a spec in Graphviz DOT, or with --nfa the NFA
+// it was determinized from.
+pub fn dot(arguments: &[String]) -> i32 {
+    let mut nfa = false;
+    let mut paths = Vec::new();
+    for argument in arguments {
+        match argument.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return SUCCESS;
+            },
+            "--nfa" => nfa = true,
+            _ if argument.starts_with('-') => return usage_error(&format!("unknown option `{}`", argument)),
+            _ => paths.push(argument.as_str()),
+        }
+    }
+    let path = match paths.as_slice() {
+        [path] => path,
+        [] => return usage_error("missing spec"),
+        _ => return usage_error("expected one spec"),
+    };
+    let lexer = match load(path) {
+        Some(lexer) => lexer,
+        None => return FAILURE,
+    };
+    print!("{}", if nfa { lexer.nfa_to_dot() } else { lexer.to_dot() });
+    SUCCESS
+}
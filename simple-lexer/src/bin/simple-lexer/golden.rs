@@ -0,0 +1,64 @@
+use simple_lexer::{
+    Outcome,
+    diff,
+    run_golden_tests,
+};
+use crate::{
+    diagnostic,
+    usage_error,
+    USAGE,
+    SUCCESS,
+    FAILURE,
+};
+
+// Runs the golden tests in each directory, printing a line per test and the
+// diff of every failure.
+pub fn test(arguments: &[String]) -> i32 {
+    let mut bless = false;
+    let mut directories = Vec::new();
+    for argument in arguments {
+        match argument.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return SUCCESS;
+            },
+            "--bless" => bless = true,
+            _ if argument.starts_with('-') => return usage_error(&format!("unknown option `{}`", argument)),
+            _ => directories.push(argument.as_str()),
+        }
+    }
+    if directories.is_empty() {
+        return usage_error("missing directory");
+    }
+    let mut status = SUCCESS;
+    let (mut passed, mut failed) = (0, 0);
+    for directory in directories {
+        let tests = match run_golden_tests(directory, bless) {
+            Ok(tests) => tests,
+            Err(error) => {
+                eprint!("{}", diagnostic::render(&error, None, ""));
+                status = FAILURE;
+                continue;
+            },
+        };
+        for test in tests {
+            match test.outcome() {
+                Outcome::Passed => println!("ok       {}", test.name()),
+                Outcome::Blessed => println!("blessed  {}", test.name()),
+                Outcome::Failed { expected, actual } => println!("FAILED   {}\n{}", test.name(), diff(expected, actual)),
+                Outcome::Invalid(error) => {
+                    println!("INVALID  {}", test.name());
+                    eprint!("{}", diagnostic::render(error, Some(test.spec()), &std::fs::read_to_string(test.spec()).unwrap_or_default()));
+                },
+            }
+            if test.is_failure() {
+                failed += 1;
+                status = FAILURE;
+            } else {
+                passed += 1;
+            }
+        }
+    }
+    println!("{} passed, {} failed", passed, failed);
+    status
+}
@@ -0,0 +1,223 @@
+use std::{
+    env,
+    fs,
+    io::{
+        self,
+        Read,
+    },
+    path::Path,
+    process,
+};
+use simple_lexer::{
+    Lexer,
+    Rule,
+    Token,
+    to_json,
+    to_json_string,
+};
+
+mod diagnostic;
+mod check;
+mod dot;
+mod repl;
+mod golden;
+
+const USAGE: &str = "\
+usage: simple-lexer [--format json|ndjson|table] SPEC INPUT...
+       simple-lexer check SPEC...
+       simple-lexer dot [--nfa] SPEC
+       simple-lexer repl SPEC
+       simple-lexer test [--bless] DIRECTORY...
+
+Lexes each input with the spec and prints the tokens. An input of `-` is
+read from standard input.
+
+commands:
+    check              report problems in specs without lexing anything
+    dot                print the DFA of a spec in Graphviz DOT, or with
+                       --nfa the NFA before determinization
+    repl               lex each line typed and show the path through the DFA
+    test               run the golden tests in directories of NAME.lex,
+                       NAME.input and NAME.tokens files, or with --bless
+                       rewrite the NAME.tokens files
+
+options:
+    --format FORMAT    json, ndjson or table (default: table)
+    -h, --help         print this message";
+
+// Exit statuses. Problems in the specs or the inputs exit with FAILURE, and
+// invalid arguments with USAGE_ERROR.
+const SUCCESS: i32 = 0;
+const FAILURE: i32 = 1;
+const USAGE_ERROR: i32 = 2;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+    Json,
+    Ndjson,
+    Table,
+}
+
+fn main() {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+    let status = match arguments.first().map(String::as_str) {
+        Some("check") => check::check(&arguments[1..]),
+        Some("dot") => dot::dot(&arguments[1..]),
+        Some("repl") => repl::repl(&arguments[1..]),
+        Some("test") => golden::test(&arguments[1..]),
+        _ => lex(&arguments),
+    };
+    process::exit(status);
+}
+
+fn lex(arguments: &[String]) -> i32 {
+    let mut format = Format::Table;
+    let mut paths = Vec::new();
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        let value = match argument.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return SUCCESS;
+            },
+            "--format" => arguments.next().map(String::as_str),
+            _ if argument.starts_with("--format=") => Some(&argument["--format=".len()..]),
+            _ if argument.starts_with('-') && argument != "-" => return usage_error(&format!("unknown option `{}`", argument)),
+            _ => {
+                paths.push(argument.as_str());
+                continue;
+            },
+        };
+        format = match value {
+            Some("json") => Format::Json,
+            Some("ndjson") => Format::Ndjson,
+            Some("table") => Format::Table,
+            Some(value) => return usage_error(&format!("unknown format `{}`", value)),
+            None => return usage_error("missing format"),
+        };
+    }
+    if paths.len() < 2 {
+        return usage_error("missing spec or input");
+    }
+    let lexer = match load(paths[0]) {
+        Some(lexer) => lexer,
+        None => return FAILURE,
+    };
+    let mut status = SUCCESS;
+    let mut records = Vec::new();
+    for &path in &paths[1..] {
+        let text = match read(path) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("error: {}: {}", path, error);
+                status = FAILURE;
+                continue;
+            },
+        };
+        match lexer.lex(&text) {
+            Ok(tokens) => records.push((path, tokens)),
+            Err(error) => {
+                eprint!("{}", diagnostic::render(&error, Some(Path::new(path)), &text));
+                status = FAILURE;
+            },
+        }
+    }
+    let labelled = paths.len() > 2;
+    match format {
+        Format::Json => {
+            let objects: Vec<String> = records.iter().flat_map(|(path, tokens)| json_lines(path, tokens, labelled)).collect();
+            if objects.is_empty() {
+                println!("[]");
+            } else {
+                println!("[\n  {}\n]", objects.join(",\n  "));
+            }
+        },
+        Format::Ndjson => {
+            for (path, tokens) in &records {
+                for object in json_lines(path, tokens, labelled) {
+                    println!("{}", object);
+                }
+            }
+        },
+        Format::Table => {
+            for (path, tokens) in &records {
+                if labelled {
+                    println!("{}:", path);
+                }
+                print!("{}", table(tokens));
+            }
+        },
+    }
+    status
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    USAGE_ERROR
+}
+
+// Builds the lexer for a spec file, printing its warnings, or its errors if
+// it has any.
+fn load(path: &str) -> Option<Lexer<String>> {
+    let source = fs::read_to_string(path).unwrap_or_default();
+    match Lexer::from_path(path) {
+        Ok(lexer) => {
+            for warning in lexer.warnings() {
+                eprint!("{}", diagnostic::render_warning(warning, Some(Path::new(path)), &source));
+            }
+            Some(lexer)
+        },
+        Err(error) => {
+            eprint!("{}", diagnostic::render(&error, Some(Path::new(path)), &source));
+            None
+        },
+    }
+}
+
+fn read(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        Ok(text)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+// With several inputs, every token also names the input it came from.
+fn json_lines(path: &str, tokens: &[Token<String>], labelled: bool) -> Vec<String> {
+    tokens.iter().map(|token| {
+        let object = to_json(token);
+        if labelled {
+            format!("{{\"path\":{},{}", to_json_string(path), &object[1..])
+        } else {
+            object
+        }
+    }).collect()
+}
+
+// One token per row, with the kind and quoted text in aligned columns and
+// the span as start and end line and column.
+fn table(tokens: &[Token<String>]) -> String {
+    let rows: Vec<(&str, String, String)> = tokens.iter().map(|token| {
+        let span = token.span();
+        let location = format!("{}:{}-{}:{}", span.start().line(), span.start().column(), span.end().line(), span.end().column());
+        (token.kind().as_str(), format!("{:?}", token.text()), location)
+    }).collect();
+    let kind_width = rows.iter().map(|(kind, _, _)| kind.chars().count()).max().unwrap_or(0);
+    let text_width = rows.iter().map(|(_, text, _)| text.chars().count()).max().unwrap_or(0);
+    let mut table = String::new();
+    for (kind, text, location) in rows {
+        table.push_str(&format!("{:kind_width$}  {:text_width$}  {}\n", kind, text, location, kind_width = kind_width, text_width = text_width));
+    }
+    table
+}
+
+// How a rule is referred to in the spec: its name, or else its token kind.
+fn rule_name(rule: &Rule<String>) -> String {
+    match (rule.name(), rule.token_kind()) {
+        (Some(name), _) => String::from(name),
+        (None, Some(token_kind)) => token_kind.clone(),
+        (None, None) => String::from("skipped rule"),
+    }
+}
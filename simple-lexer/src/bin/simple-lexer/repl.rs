@@ -0,0 +1,103 @@
+use std::io::{
+    self,
+    BufRead,
+    Write,
+};
+use simple_lexer::Step;
+use crate::{
+    diagnostic,
+    load,
+    rule_name,
+    table,
+    usage_error,
+    USAGE,
+    SUCCESS,
+    FAILURE,
+};
+
+const HELP: &str = "\
+Type a line to lex it. Commands:
+    :reload    load the spec again, keeping the current one if it has errors
+    :help      print this message
+    :quit      exit";
+
+// Lexes each line read from standard input, printing the tokens and every
+// DFA transition taken, with the rules still alive after each character.
+pub fn repl(arguments: &[String]) -> i32 {
+    let mut path = None;
+    for argument in arguments {
+        match argument.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return SUCCESS;
+            },
+            _ if argument.starts_with('-') => return usage_error(&format!("unknown option `{}`", argument)),
+            _ if path.is_some() => return usage_error("more than one spec"),
+            _ => path = Some(argument.as_str()),
+        }
+    }
+    let path = match path {
+        Some(path) => path,
+        None => return usage_error("missing spec"),
+    };
+    let mut lexer = match load(path) {
+        Some(lexer) => lexer,
+        None => return FAILURE,
+    };
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        let _ = io::stdout().flush();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(error)) => {
+                eprintln!("error: {}", error);
+                return FAILURE;
+            },
+            None => {
+                println!();
+                return SUCCESS;
+            },
+        };
+        match line.trim() {
+            ":quit" | ":q" => return SUCCESS,
+            ":help" => println!("{}", HELP),
+            ":reload" => {
+                if let Some(reloaded) = load(path) {
+                    lexer = reloaded;
+                    println!("reloaded {}", path);
+                }
+            },
+            command if command.starts_with(':') => eprintln!("error: unknown command `{}`, try :help", command),
+            _ => {
+                let (steps, tokens) = lexer.trace(&line);
+                match tokens {
+                    Ok(tokens) => print!("{}", table(&tokens)),
+                    Err(error) => eprint!("{}", diagnostic::render(&error, None, &line)),
+                }
+                print!("{}", path_table(&steps));
+            },
+        }
+    }
+}
+
+// One transition per row, with a blank row between tokens.
+fn path_table(steps: &[Step<String>]) -> String {
+    let mut rendered = String::new();
+    for (step_index, step) in steps.iter().enumerate() {
+        if step.starts_token() && step_index > 0 {
+            rendered.push('\n');
+        }
+        let alive_rules: Vec<String> = step.alive_rules().iter().map(rule_name).collect();
+        rendered.push_str(&format!(
+            "  {} {:>3} -{:?}-> {:<3} {}\n",
+            step.mode(),
+            step.source_index(),
+            step.character(),
+            step.target_index(),
+            alive_rules.join(", ")
+        ));
+    }
+    rendered
+}
@@ -0,0 +1,182 @@
+use regular_expression::{
+    Expression,
+    Re,
+};
+use simple_lexer_bootstrap::{
+    Rule,
+    DEFAULT_MODE,
+};
+use crate::{
+    error::BuildError,
+    grammar::escape,
+    Lexer,
+};
+
+// A pattern given to a LexerBuilder, either regular expression text or an
+// already built expression.
+pub trait IntoExpression {
+    fn into_expression(self) -> Result<Expression, &'static str>;
+}
+
+impl IntoExpression for &str {
+    fn into_expression(self) -> Result<Expression, &'static str> {
+        Ok(Re::new(self)?.into_expression())
+    }
+}
+
+impl IntoExpression for Expression {
+    fn into_expression(self) -> Result<Expression, &'static str> {
+        Ok(self)
+    }
+}
+
+// Builds a lexer rule by rule without writing spec text. Rules take
+// precedence in the order they are added. Options such as `priority` apply to
+// the most recently added rule, and `mode` applies to every rule added after
+// it. Errors are collected and reported by `build`.
+pub struct LexerBuilder<T> {
+    rules: Vec<Option<Rule<T>>>,
+    mode: String,
+    errors: Vec<BuildError>,
+}
+
+impl<T: Clone + Ord> LexerBuilder<T> {
+    pub fn new() -> LexerBuilder<T> {
+        LexerBuilder {
+            rules: Vec::new(),
+            mode: String::from(DEFAULT_MODE),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn token<P: IntoExpression>(self, token_kind: T, pattern: P) -> LexerBuilder<T> {
+        self.rule(Some(token_kind), pattern.into_expression())
+    }
+
+    pub fn literal(self, token_kind: T, literal: &str) -> LexerBuilder<T> {
+        let expression = if literal.is_empty() {
+            Err("empty literal")
+        } else {
+            escape(literal).as_str().into_expression()
+        };
+        self.rule(Some(token_kind), expression)
+    }
+
+    // Adds a rule which consumes its matches without producing tokens.
+    pub fn skip<P: IntoExpression>(self, pattern: P) -> LexerBuilder<T> {
+        self.rule(None, pattern.into_expression())
+    }
+
+    // Makes the following rules active only in the given mode. Rules added
+    // before any mode are in the default mode, whose name is reserved.
+    pub fn mode(mut self, mode: &str) -> LexerBuilder<T> {
+        if mode == DEFAULT_MODE {
+            self.errors.push(BuildError::new("reserved mode name", None));
+        }
+        self.mode = String::from(mode);
+        self
+    }
+
+    // Switches to the given mode after the last rule matches.
+    pub fn next_mode(self, next_mode: &str) -> LexerBuilder<T> {
+        self.modify(|rule| rule.with_next_mode(next_mode))
+    }
+
+    pub fn priority(self, priority: i64) -> LexerBuilder<T> {
+        self.modify(|rule| rule.with_priority(priority))
+    }
+
+    pub fn name(self, name: &str) -> LexerBuilder<T> {
+        self.modify(|rule| rule.with_name(name))
+    }
+
+    // Fails with the first error by rule index, after errors not tied to a
+    // rule. A mode is defined once some rule is active in it, so switching to
+    // a mode without rules is an error.
+    pub fn build(self) -> Result<Lexer<T>, BuildError> {
+        let mut errors = self.errors;
+        for (rule_index, rule) in self.rules.iter().enumerate() {
+            if let Some(next_mode) = rule.as_ref().and_then(Rule::next_mode) {
+                if next_mode != DEFAULT_MODE && !self.rules.iter().flatten().any(|rule| rule.mode() == next_mode) {
+                    errors.push(BuildError::new("undefined mode", Some(rule_index)));
+                }
+            }
+        }
+        if let Some(error) = errors.into_iter().min_by_key(BuildError::rule_index) {
+            return Err(error);
+        }
+        Lexer::from_rules(self.rules.into_iter().flatten().collect()).map_err(|message| BuildError::new(message, None))
+    }
+
+    // Rules with invalid patterns are kept as placeholders so every error
+    // refers to the index the rule was added at.
+    fn rule(mut self, token_kind: Option<T>, expression: Result<Expression, &'static str>) -> LexerBuilder<T> {
+        match expression {
+            Ok(expression) => self.rules.push(Some(Rule::new(expression, token_kind).with_mode(&self.mode))),
+            Err(message) => {
+                self.errors.push(BuildError::new(message, Some(self.rules.len())));
+                self.rules.push(None);
+            },
+        }
+        self
+    }
+
+    fn modify<F: FnOnce(Rule<T>) -> Rule<T>>(mut self, modify: F) -> LexerBuilder<T> {
+        match self.rules.pop() {
+            Some(rule) => self.rules.push(rule.map(modify)),
+            None => self.errors.push(BuildError::new("option without rule", None)),
+        }
+        self
+    }
+}
+
+impl<T: Clone + Ord> Default for LexerBuilder<T> {
+    fn default() -> LexerBuilder<T> {
+        LexerBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        LexerBuilder,
+        Token,
+        DEFAULT_MODE,
+    };
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn builder_switches_modes_and_reports_the_failing_rule() -> Result<()> {
+        let lexer = LexerBuilder::new()
+            .literal("ARROW", "=>")
+            .token("IDENTIFIER", "[a-z]+")
+            .literal("QUOTE", "\"").next_mode("string")
+            .skip(" ")
+            .mode("string")
+            .token("TEXT", "[^\"]+")
+            .literal("QUOTE", "\"").next_mode(DEFAULT_MODE)
+            .build()?;
+        let expected = vec![
+            Token::new("IDENTIFIER", "a"),
+            Token::new("ARROW", "=>"),
+            Token::new("QUOTE", "\""),
+            Token::new("TEXT", "b c"),
+            Token::new("QUOTE", "\""),
+        ];
+        assert_eq!(expected, lexer.lex("a => \"b c\"")?);
+        let error = LexerBuilder::new()
+            .token("IDENTIFIER", "[a-z]+")
+            .literal("ARROW", "")
+            .token("TEXT", "(")
+            .build()
+            .err()
+            .ok_or("expected error")?;
+        assert_eq!(("empty literal", Some(1)), (error.message(), error.rule_index()));
+        let error = LexerBuilder::new().literal("QUOTE", "\"").next_mode("string").build().err().ok_or("expected error")?;
+        assert_eq!(("undefined mode", Some(0)), (error.message(), error.rule_index()));
+        let error = LexerBuilder::new().literal("QUOTE", "\"").mode(DEFAULT_MODE).build().err().ok_or("expected error")?;
+        assert_eq!(("reserved mode name", None), (error.message(), error.rule_index()));
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use simple_lexer_bootstrap::Span;
+
+// An error in a spec or in lexed text. Where recovery was possible, the
+// first error also holds every other error found after it. With the serde
+// feature, an error serializes as `{"message": "...", "path": ..., "span": ...,
+// "found": ..., "note": ..., "others": [...]}`, with null for whatever is
+// missing. Messages the lexer produces are static, while deserialized ones
+// are owned. The span is boxed to keep results small.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Error {
+    message: Cow<'static, str>,
+    path: Option<PathBuf>,
+    span: Option<Box<Span>>,
+    found: Option<String>,
+    note: Option<Box<Error>>,
+    others: Vec<Error>,
+}
+
+impl Error {
+    pub(crate) fn new<M: Into<Cow<'static, str>>>(message: M) -> Error {
+        Error { message: message.into(), path: None, span: None, found: None, note: None, others: Vec::new() }
+    }
+
+    // Orders errors by position and folds them into the first, or returns
+    // None if there are none.
+    pub(crate) fn from_errors(mut errors: Vec<Error>) -> Option<Error> {
+        errors.sort_by_key(|error| error.span.as_ref().map(|span| *span.start()));
+        let mut errors = errors.into_iter();
+        let mut first = errors.next()?;
+        first.others.extend(errors);
+        Some(first)
+    }
+
+    // Attaches the file the error occurred in, unless a more specific one,
+    // such as an included file, was already attached.
+    pub(crate) fn in_file(mut self, path: Option<&Path>) -> Error {
+        if self.path.is_none() {
+            self.path = path.map(Path::to_path_buf);
+        }
+        self.others = self.others.into_iter().map(|other| other.in_file(path)).collect();
+        self
+    }
+
+    pub(crate) fn at(mut self, span: &Span) -> Error {
+        if self.span.is_none() {
+            self.span = Some(Box::new(*span));
+        }
+        self
+    }
+
+    // The offending text.
+    pub(crate) fn found(mut self, text: &str) -> Error {
+        self.found = Some(String::from(text));
+        self
+    }
+
+    // Points at a related location, such as an earlier production the error
+    // conflicts with.
+    pub(crate) fn with_note(mut self, note: Error) -> Error {
+        self.note = Some(Box::new(note));
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_deref()
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.found.as_deref()
+    }
+
+    pub fn note(&self) -> Option<&Error> {
+        self.note.as_deref()
+    }
+
+    // This error followed by every other error found while recovering.
+    pub fn errors<'a>(&'a self) -> impl Iterator<Item = &'a Error> + 'a {
+        std::iter::once(self).chain(self.others.iter())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        if let Some(span) = &self.span {
+            write!(f, "{}:{}:", span.start().line(), span.start().column())?;
+        }
+        if self.path.is_some() || self.span.is_some() {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(found) = &self.found {
+            write!(f, ", found `{}`", found)?;
+        }
+        if let Some(note) = &self.note {
+            write!(f, "\n{}", note)?;
+        }
+        for other in &self.others {
+            write!(f, "\n{}", other)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<&'static str> for Error {
+    fn from(message: &'static str) -> Error {
+        Error::new(message)
+    }
+}
+
+// An error building a lexer with a LexerBuilder. The rule index counts rules
+// in the order they were added, and is None for errors not tied to a rule.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BuildError {
+    message: Cow<'static, str>,
+    rule_index: Option<usize>,
+}
+
+impl BuildError {
+    pub(crate) fn new(message: &'static str, rule_index: Option<usize>) -> BuildError {
+        BuildError { message: Cow::Borrowed(message), rule_index }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn rule_index(&self) -> Option<usize> {
+        self.rule_index
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(rule_index) = self.rule_index {
+            write!(f, "rule {}: ", rule_index)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::Lexer;
+    use super::Error;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn errors_round_trip_through_serde() -> Result<()> {
+        let error = Lexer::<String>::new("/a/ => A\n/b/ A;\n/ / => ;\n").err().ok_or("no error")?;
+        assert_eq!(2, error.errors().count());
+        let json = serde_json::to_string(&error)?;
+        assert_eq!(error, serde_json::from_str::<Error>(&json)?);
+        Ok(())
+    }
+}
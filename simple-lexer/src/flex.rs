@@ -0,0 +1,444 @@
+use std::{
+    collections::BTreeMap as Map,
+    iter::Peekable,
+    str::Chars,
+};
+use simple_lexer_bootstrap::{
+    Location,
+    Span,
+};
+use crate::{
+    error::Error,
+    grammar::escape_character,
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+struct Line<'a> {
+    text: &'a str,
+    span: Span,
+}
+
+struct Importer {
+    spec: String,
+    reports: Vec<Error>,
+    // spec names of the flex definitions
+    definitions: Map<String, String>,
+    // patterns of rules whose action is `|`, sharing the next rule's action
+    pending: Vec<String>,
+}
+
+// Translates the definitions and rules sections of a flex scanner into a
+// spec. A rule becomes a production of the token kind its action returns, or
+// a consumption if its action returns nothing. Whatever cannot be translated,
+// such as start conditions, trailing context, anchors, REJECT and C code, is
+// reported against its line, and the rules using it are left out.
+pub fn import_flex(text: &str) -> Result<(String, Vec<Error>)> {
+    let lines = as_lines(text);
+    let mut importer = Importer { spec: String::new(), reports: Vec::new(), definitions: Map::new(), pending: Vec::new() };
+    let mut section = 0;
+    let mut index = 0;
+    while index < lines.len() {
+        let line = &lines[index];
+        index += 1;
+        if line.text.trim_end() == "%%" {
+            section += 1;
+            if section == 2 {
+                if lines[index..].iter().any(|line| !line.text.trim().is_empty()) {
+                    importer.report("user code ignored", line);
+                }
+                break;
+            }
+            continue;
+        }
+        if line.text.trim().is_empty() {
+            continue;
+        }
+        if line.text.starts_with("%{") {
+            importer.report("code block ignored", line);
+            while index < lines.len() && !lines[index - 1].text.trim_end().ends_with("%}") {
+                index += 1;
+            }
+            continue;
+        }
+        if line.text.trim_start().starts_with("/*") {
+            while index < lines.len() && !lines[index - 1].text.contains("*/") {
+                index += 1;
+            }
+            continue;
+        }
+        if line.text.starts_with(char::is_whitespace) {
+            importer.report("code ignored", line);
+            continue;
+        }
+        if section == 0 {
+            importer.definition(line);
+        } else {
+            // actions in braces may continue over several lines
+            let mut action = String::new();
+            let (pattern, rest) = split_pattern(line.text);
+            action.push_str(rest.trim());
+            while depth(&action) > 0 && index < lines.len() {
+                action.push('\n');
+                action.push_str(lines[index].text.trim());
+                index += 1;
+            }
+            importer.rule(line, pattern, &action);
+        }
+    }
+    if section == 0 {
+        return Err(Error::new("missing rules section"));
+    }
+    if let Some(line) = lines.last() {
+        if !importer.pending.is_empty() {
+            importer.report("rule without action", line);
+        }
+    }
+    Ok((importer.spec, importer.reports))
+}
+
+impl Importer {
+    fn report(&mut self, message: &'static str, line: &Line) {
+        self.reports.push(Error::new(message).at(&line.span).found(line.text.trim_end()));
+    }
+
+    // name definition
+    fn definition(&mut self, line: &Line) {
+        if line.text.starts_with("%x") || line.text.starts_with("%s") {
+            return self.report("start conditions unsupported", line);
+        }
+        if line.text.starts_with("%option") {
+            return;
+        }
+        if line.text.starts_with('%') {
+            return self.report("unsupported directive", line);
+        }
+        let name_length = line.text.find(char::is_whitespace).unwrap_or(line.text.len());
+        let (name, definition) = line.text.split_at(name_length);
+        let spec_name = name.to_uppercase().replace('-', "_");
+        if !spec_name.starts_with(|character: char| character.is_ascii_uppercase()) || !spec_name.chars().all(|character| character.is_ascii_alphanumeric() || character == '_') {
+            return self.report("invalid definition name", line);
+        }
+        match translate(definition.trim(), &self.definitions) {
+            Ok(regular_expression) => {
+                self.spec.push_str(&format!("{} = /{}/;\n", spec_name, regular_expression));
+                self.definitions.insert(String::from(name), spec_name);
+            },
+            Err(message) => self.report(message, line),
+        }
+    }
+
+    fn rule(&mut self, line: &Line, pattern: &str, action: &str) {
+        if pattern.starts_with("<<EOF>>") {
+            return self.report("end of file rules unsupported", line);
+        }
+        if pattern.starts_with('<') {
+            return self.report("start conditions unsupported", line);
+        }
+        let regular_expression = match translate(pattern, &self.definitions) {
+            Ok(regular_expression) => regular_expression,
+            Err(message) => return self.report(message, line),
+        };
+        self.pending.push(regular_expression);
+        if action == "|" {
+            return;
+        }
+        let patterns = std::mem::take(&mut self.pending);
+        if action.contains("REJECT") {
+            return self.report("REJECT unsupported", line);
+        }
+        let token_kind = match returned(action) {
+            Some(token_kind) if token_kind.starts_with(|character: char| character.is_ascii_uppercase()) && token_kind.chars().all(|character| character.is_ascii_uppercase() || character.is_ascii_digit() || character == '_') => token_kind,
+            Some(_) => return self.report("invalid token kind", line),
+            None => "",
+        };
+        // anything in the action besides a return of the token kind is lost
+        let statement = if token_kind.is_empty() { String::new() } else { format!("return {};", token_kind) };
+        if strip(action) != statement {
+            self.report("action code ignored", line);
+        }
+        for regular_expression in patterns {
+            self.spec.push_str(&format!("/{}/ => {};\n", regular_expression, token_kind));
+        }
+    }
+}
+
+fn as_lines(text: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut index = 0;
+    for (line_index, line) in text.split('\n').enumerate() {
+        let start = Location::new(index, line_index + 1, 1);
+        let end = Location::new(index + line.len(), line_index + 1, line.chars().count() + 1);
+        lines.push(Line { text: line.trim_end_matches('\r'), span: Span::new(start, end) });
+        index += line.len() + 1;
+    }
+    lines
+}
+
+// Splits a rule line at the first whitespace outside quotes and character
+// classes.
+fn split_pattern(text: &str) -> (&str, &str) {
+    let mut in_quotes = false;
+    let mut in_class = false;
+    let mut characters = text.char_indices();
+    while let Some((index, character)) = characters.next() {
+        match character {
+            '\\' => {
+                characters.next();
+            },
+            '"' if !in_class => in_quotes = !in_quotes,
+            '[' if !in_quotes => in_class = true,
+            ']' if !in_quotes => in_class = false,
+            _ if character.is_whitespace() && !in_quotes && !in_class => return text.split_at(index),
+            _ => {},
+        }
+    }
+    (text, "")
+}
+
+fn depth(action: &str) -> i64 {
+    action.chars().map(|character| match character {
+        '{' => 1,
+        '}' => -1,
+        _ => 0,
+    }).sum()
+}
+
+// The identifier an action returns, if any.
+fn returned(action: &str) -> Option<&str> {
+    let start = action.find("return")? + "return".len();
+    let rest = action[start..].trim_start();
+    let end = rest.find(|character: char| !(character.is_ascii_alphanumeric() || character == '_')).unwrap_or(rest.len());
+    Some(&rest[..end]).filter(|token_kind| !token_kind.is_empty())
+}
+
+// An action without braces, comments and whitespace, so `{ return IF; }`
+// becomes `return IF;` and an empty action becomes empty.
+fn strip(action: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = action.trim();
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = rest[start..].find("*/").map_or("", |end| &rest[start + end + 2..]);
+    }
+    stripped.push_str(rest);
+    let stripped = stripped.trim();
+    let stripped = if stripped.starts_with('{') && stripped.ends_with('}') { &stripped[1..stripped.len() - 1] } else { stripped };
+    let stripped = stripped.trim();
+    if stripped == ";" { String::new() } else { stripped.split_whitespace().collect::<Vec<_>>().join(" ") }
+}
+
+// Translates a flex pattern into spec regular expression syntax.
+fn translate(pattern: &str, definitions: &Map<String, String>) -> std::result::Result<String, &'static str> {
+    if pattern.is_empty() {
+        return Err("empty pattern");
+    }
+    if pattern.starts_with('^') {
+        return Err("anchors unsupported");
+    }
+    let mut translated = String::new();
+    let mut characters = pattern.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            '"' => loop {
+                match characters.next() {
+                    Some('"') => break,
+                    Some('\\') => translated.push_str(&escape_character(unescape(&mut characters)?)),
+                    Some(character) => translated.push_str(&escape_character(character)),
+                    None => return Err("unterminated string"),
+                }
+            },
+            '[' => translated.push_str(&class(&mut characters)?),
+            '{' if characters.peek().is_some_and(char::is_ascii_digit) => {
+                translated.push('{');
+                loop {
+                    match characters.next() {
+                        Some('}') => break,
+                        Some(character) => translated.push(character),
+                        None => return Err("unterminated repetition"),
+                    }
+                }
+                translated.push('}');
+            },
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match characters.next() {
+                        Some('}') => break,
+                        Some(character) => name.push(character),
+                        None => return Err("unterminated definition reference"),
+                    }
+                }
+                let spec_name = definitions.get(&name).ok_or("undefined definition")?;
+                translated.push_str(&format!("{{{}}}", spec_name));
+            },
+            '\\' => translated.push_str(&escape_character(unescape(&mut characters)?)),
+            '.' => translated.push_str("[^\\n]"),
+            '$' if characters.peek().is_none() => return Err("anchors unsupported"),
+            '/' => return Err("trailing context unsupported"),
+            '*' | '+' | '?' | '|' | '(' | ')' => translated.push(character),
+            _ => translated.push_str(&escape_character(character)),
+        }
+    }
+    Ok(translated)
+}
+
+// Translates a character class after its opening bracket.
+fn class(characters: &mut Peekable<Chars>) -> std::result::Result<String, &'static str> {
+    let mut translated = String::from("[");
+    if characters.peek() == Some(&'^') {
+        characters.next();
+        translated.push('^');
+    }
+    let mut first = true;
+    loop {
+        let character = characters.next().ok_or("unterminated character class")?;
+        match character {
+            ']' if !first => break,
+            '\\' => translated.push_str(&escape_character(unescape(characters)?)),
+            '[' if characters.peek() == Some(&':') => {
+                characters.next();
+                let mut name = String::new();
+                for character in characters.by_ref() {
+                    if character == ':' {
+                        break;
+                    }
+                    name.push(character);
+                }
+                if characters.next() != Some(']') {
+                    return Err("unterminated character class");
+                }
+                translated.push_str(&posix_class(&name)?);
+            },
+            // a hyphen between two characters is a range
+            '-' if !first && characters.peek().is_some_and(|&next| next != ']') => translated.push('-'),
+            // digits and commas cannot read as a repetition inside a class
+            '0'..='9' | ',' => translated.push(character),
+            _ => translated.push_str(&escape_character(character)),
+        }
+        first = false;
+    }
+    translated.push(']');
+    Ok(translated)
+}
+
+fn posix_class(name: &str) -> std::result::Result<String, &'static str> {
+    Ok(String::from(match name {
+        "alpha" => "a-zA-Z",
+        "digit" => "0-9",
+        "alnum" => "a-zA-Z0-9",
+        "upper" => "A-Z",
+        "lower" => "a-z",
+        "xdigit" => "0-9a-fA-F",
+        "blank" => " \\t",
+        "space" => " \\t\\n\\r\\u000B\\u000C",
+        "cntrl" => "\\u0000-\\u001F\\u007F",
+        "print" => " -~",
+        "graph" => "!-~",
+        "punct" => "!-\\/:-@\\[-`\\{-~",
+        _ => return Err("unknown character class"),
+    }))
+}
+
+// The character a flex escape stands for, after its backslash.
+fn unescape(characters: &mut Peekable<Chars>) -> std::result::Result<char, &'static str> {
+    let (radix, digits) = match characters.next().ok_or("trailing backslash")? {
+        'n' => return Ok('\n'),
+        't' => return Ok('\t'),
+        'r' => return Ok('\r'),
+        'f' => return Ok('\u{C}'),
+        'v' => return Ok('\u{B}'),
+        'a' => return Ok('\u{7}'),
+        'b' => return Ok('\u{8}'),
+        'x' => (16, 2),
+        digit @ '0'..='7' => {
+            let mut value = digit.to_digit(8).expect("octal digit");
+            for _ in 1..3 {
+                match characters.peek().and_then(|digit| digit.to_digit(8)) {
+                    Some(digit) => value = value * 8 + digit,
+                    None => break,
+                }
+                characters.next();
+            }
+            return std::char::from_u32(value).ok_or("invalid escape");
+        },
+        character => return Ok(character),
+    };
+    let mut value = 0;
+    for _ in 0..digits {
+        match characters.peek().and_then(|digit| digit.to_digit(radix)) {
+            Some(digit) => value = value * radix + digit,
+            None => break,
+        }
+        characters.next();
+    }
+    std::char::from_u32(value).ok_or("invalid escape")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        util::tokens,
+        Lexer,
+    };
+    use super::import_flex;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn flex_scanners_import_with_reports_for_unsupported_parts() -> Result<()> {
+        let text = concat!(
+            "%{\n",
+            "#include \"y.tab.h\"\n",
+            "%}\n",
+            "%option noyywrap\n",
+            "digit    [0-9]\n",
+            "%x COMMENT\n",
+            "%%\n",
+            "\"if\"            { return IF; }\n",
+            "{digit}+        { yylval = atoi(yytext); return NUMBER; }\n",
+            "[a-z]+          return IDENTIFIER;\n",
+            "[ \\t\\n]+        ;\n",
+            "\"/*\"            BEGIN(COMMENT);\n",
+            "<COMMENT>\"*/\"   BEGIN(INITIAL);\n",
+            ".               REJECT;\n",
+            "%%\n",
+            "int main() {}\n",
+        );
+        let expected = concat!(
+            "DIGIT = /[0-9]/;\n",
+            "/if/ => IF;\n",
+            "/{DIGIT}+/ => NUMBER;\n",
+            "/[a-z]+/ => IDENTIFIER;\n",
+            "/[ \\t\\n]+/ => ;\n",
+            "/\\/\\*/ => ;\n",
+        );
+        let (spec, reports) = import_flex(text)?;
+        assert_eq!(expected, spec);
+        let reports = reports.iter().map(|report| (report.message(), report.span().map(|span| span.start().line()))).collect::<Vec<_>>();
+        assert_eq!(vec![
+            ("code block ignored", Some(1)),
+            ("start conditions unsupported", Some(6)),
+            ("action code ignored", Some(9)),
+            ("action code ignored", Some(12)),
+            ("start conditions unsupported", Some(13)),
+            ("REJECT unsupported", Some(14)),
+            ("user code ignored", Some(15)),
+        ], reports);
+        let lexer: Lexer<String> = Lexer::from_flex(text)?;
+        assert_eq!(tokens(&[("IF", "if"), ("NUMBER", "42"), ("IDENTIFIER", "abc")]), lexer.lex("if 42 abc")?);
+        assert_eq!(7, lexer.warnings().len());
+        Ok(())
+    }
+
+    #[test]
+    fn quoted_braces_commas_and_digits_import_literally() -> Result<()> {
+        let text = "%%\n\"a{1,2}\"    return BRACES;\n";
+        let (spec, _) = import_flex(text)?;
+        assert_eq!("/a\\{\\u0031\\u002C\\u0032\\}/ => BRACES;\n", spec);
+        let lexer: Lexer<String> = Lexer::from_flex(text)?;
+        assert_eq!(tokens(&[("BRACES", "a{1,2}")]), lexer.lex("a{1,2}")?);
+        assert!(lexer.lex("aa").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use std::char;
+use simple_lexer_bootstrap::{
+    Lexer as LexerBootstrap,
+    Token,
+};
+use crate::{
+    error::Error,
+    grammar::{
+        escape_character,
+        LEXER_PRODUCTIONS,
+        TokenKind::{
+            self,
+            *,
+        },
+    },
+    spec::lex,
+    syntax::check,
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+const INDENT: &str = "    ";
+
+// Re-emits a spec in canonical layout. Every statement gets its own line,
+// attributes share the line of the statement they apply to, and the `=>` of
+// consecutive productions and keywords, like the `=` of consecutive
+// definitions, are aligned. Numeric escapes in regular expressions are
+// replaced by the characters they stand for where possible. Comments and
+// single blank lines between statements are kept, so formatting only changes
+// layout and never the productions.
+pub fn format(text: &str) -> Result<String> {
+    let lexer = LexerBootstrap::new(LEXER_PRODUCTIONS.clone());
+    let (tokens, mut errors) = lex(&lexer, text);
+    let code: Vec<Token<TokenKind>> = tokens.iter().filter(|token| *token.kind() != COMMENT).cloned().collect();
+    errors.extend(check(&code));
+    if let Some(error) = Error::from_errors(errors) {
+        return Err(error);
+    }
+    let mut formatter = Formatter { tokens: &tokens, index: 0, lines: Vec::new(), last_line: None, attachable: false, blank: false };
+    formatter.statements();
+    Ok(render(&formatter.lines))
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Alignment {
+    Definition,
+    Production,
+    Keyword,
+}
+
+struct Line {
+    indent: usize,
+    code: String,
+    aligned: Option<(Alignment, String)>,
+    comment: Option<String>,
+    blank_before: bool,
+}
+
+struct Formatter<'a> {
+    tokens: &'a [Token<TokenKind>],
+    index: usize,
+    lines: Vec<Line>,
+    // the source line of the last token consumed
+    last_line: Option<usize>,
+    // whether the last line pushed ends with the last token consumed, so a
+    // comment on the same source line can trail it
+    attachable: bool,
+    // whether a blank line precedes the next line pushed
+    blank: bool,
+}
+
+impl<'a> Formatter<'a> {
+    // The spec already passed the syntax check, so every statement is
+    // complete.
+    fn statements(&mut self) {
+        let mut attributes = Vec::new();
+        while let Some(token) = self.next(0) {
+            match token.kind() {
+                ATTRIBUTE => {
+                    let mut attribute = String::from(token.text());
+                    if self.peek() == Some(LEFT_PARENTHESIS) {
+                        for _ in 0..3 {
+                            attribute.push_str(self.code(0).text());
+                        }
+                    }
+                    attributes.push(attribute);
+                },
+                INCLUDE => {
+                    let path = self.code(0).text();
+                    self.code(0);
+                    self.push(0, prefixed(&attributes, &format!("include {};", path)), None);
+                },
+                TOKEN_KIND => {
+                    self.code(0);
+                    let regular_expression = normalize(self.code(0).text());
+                    self.code(0);
+                    self.push(0, prefixed(&attributes, token.text()), Some((Alignment::Definition, format!("= {};", regular_expression))));
+                },
+                REGULAR_EXPRESSION | STRING_LITERAL => {
+                    let pattern = if *token.kind() == REGULAR_EXPRESSION {
+                        normalize(token.text())
+                    } else {
+                        String::from(token.text())
+                    };
+                    self.code(0);
+                    let token_kind = self.code(0);
+                    let production = if *token_kind.kind() == SEMICOLON {
+                        String::from("=> ;")
+                    } else {
+                        self.code(0);
+                        format!("=> {};", token_kind.text())
+                    };
+                    self.push(0, prefixed(&attributes, &pattern), Some((Alignment::Production, production)));
+                },
+                KEYWORDS => {
+                    let token_kind = self.code(0).text();
+                    self.code(0);
+                    self.push(0, prefixed(&attributes, &format!("keywords {} {{", token_kind)), None);
+                    loop {
+                        let keyword = self.code(1).text();
+                        self.code(1);
+                        let keyword_token_kind = self.code(1).text();
+                        if *self.code(1).kind() == RIGHT_BRACE {
+                            self.push(1, String::from(keyword), Some((Alignment::Keyword, format!("=> {}", keyword_token_kind))));
+                            self.push(0, String::from("}"), None);
+                            break;
+                        }
+                        self.push(1, String::from(keyword), Some((Alignment::Keyword, format!("=> {},", keyword_token_kind))));
+                    }
+                },
+                _ => {},
+            }
+            if *token.kind() != ATTRIBUTE {
+                attributes.clear();
+            }
+        }
+    }
+
+    // The next token, with comments before it pushed as lines of their own or
+    // appended to the line they trail.
+    fn next(&mut self, indent: usize) -> Option<&'a Token<TokenKind>> {
+        let tokens = self.tokens;
+        while let Some(token) = tokens.get(self.index) {
+            self.index += 1;
+            let line = token.span().start().line();
+            let same_line = self.last_line == Some(line);
+            if self.last_line.is_some_and(|last_line| line > last_line + 1) {
+                self.blank = true;
+            }
+            self.last_line = Some(token.span().end().line());
+            if *token.kind() != COMMENT {
+                self.attachable = false;
+                return Some(token);
+            }
+            let comment = String::from(token.text().trim_end());
+            match self.lines.last_mut() {
+                Some(last) if self.attachable && same_line => last.comment = Some(comment),
+                _ => {
+                    self.push(indent, String::new(), None);
+                    if let Some(last) = self.lines.last_mut() {
+                        last.comment = Some(comment);
+                    }
+                },
+            }
+        }
+        None
+    }
+
+    fn code(&mut self, indent: usize) -> &'a Token<TokenKind> {
+        self.next(indent).expect("incomplete statement")
+    }
+
+    fn peek(&self) -> Option<TokenKind> {
+        self.tokens[self.index..].iter().map(|token| *token.kind()).find(|&kind| kind != COMMENT)
+    }
+
+    fn push(&mut self, indent: usize, code: String, aligned: Option<(Alignment, String)>) {
+        let blank_before = self.blank && !self.lines.is_empty();
+        self.lines.push(Line { indent, code, aligned, comment: None, blank_before });
+        self.blank = false;
+        self.attachable = true;
+    }
+}
+
+fn prefixed(attributes: &[String], code: &str) -> String {
+    let mut prefixed = String::new();
+    for attribute in attributes {
+        prefixed.push_str(attribute);
+        prefixed.push(' ');
+    }
+    prefixed.push_str(code);
+    prefixed
+}
+
+// Aligns runs of lines with the same alignment, which blank lines and lines
+// without alignment end. Comment lines do not end a run.
+fn render(lines: &[Line]) -> String {
+    let mut widths = vec![0; lines.len()];
+    let mut start = 0;
+    while start < lines.len() {
+        let alignment = match &lines[start].aligned {
+            Some((alignment, _)) => *alignment,
+            None => {
+                start += 1;
+                continue;
+            },
+        };
+        let mut end = start;
+        let mut width = 0;
+        while end < lines.len() && (end == start || !lines[end].blank_before) {
+            match &lines[end].aligned {
+                Some((line_alignment, _)) if *line_alignment == alignment => width = width.max(lines[end].code.chars().count()),
+                None if lines[end].code.is_empty() => {},
+                _ => break,
+            }
+            end += 1;
+        }
+        widths[start..end].fill(width);
+        start = end;
+    }
+    let mut rendered = String::new();
+    for (line, width) in lines.iter().zip(widths) {
+        if line.blank_before {
+            rendered.push('\n');
+        }
+        let mut text = INDENT.repeat(line.indent);
+        text.push_str(&line.code);
+        if let Some((_, aligned)) = &line.aligned {
+            let padding = width - line.code.chars().count();
+            text.push_str(&" ".repeat(padding + 1));
+            text.push_str(aligned);
+        }
+        if let Some(comment) = &line.comment {
+            if !line.code.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(comment);
+        }
+        rendered.push_str(&text);
+        rendered.push('\n');
+    }
+    rendered
+}
+
+// Rewrites octal, hexadecimal and unicode escapes in a regular expression
+// token. Characters which need no escape are written as themselves, escapable
+// and control characters get their short escape, and anything else, such as
+// digits and commas which mean something inside repetitions, gets a unicode
+// escape. Escapes of invalid characters are left as they are.
+fn normalize(regular_expression: &str) -> String {
+    let mut normalized = String::new();
+    let mut characters = regular_expression.chars().peekable();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            normalized.push(character);
+            continue;
+        }
+        let (radix, digits, mut escape) = match characters.peek().cloned() {
+            Some('0'..='7') => (8, 3, String::from("\\")),
+            Some('x') => (16, 2, String::from("\\x")),
+            Some('u') => (16, 4, String::from("\\u")),
+            Some('U') => (16, 8, String::from("\\U")),
+            Some(escaped) => {
+                characters.next();
+                normalized.push('\\');
+                normalized.push(escaped);
+                continue;
+            },
+            None => {
+                normalized.push('\\');
+                continue;
+            },
+        };
+        if radix == 16 {
+            characters.next();
+        }
+        let mut value = String::new();
+        while value.len() < digits && characters.peek().is_some_and(|digit| digit.is_digit(radix)) {
+            value.push(characters.next().expect("peeked character"));
+        }
+        escape.push_str(&value);
+        match u32::from_str_radix(&value, radix).ok().and_then(char::from_u32) {
+            Some(character) => normalized.push_str(&escape_character(character)),
+            None => normalized.push_str(&escape),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        grammar::as_productions,
+        spec::parse,
+    };
+    use super::format;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn formatting_aligns_productions_keeps_comments_and_preserves_meaning() -> Result<()> {
+        let text = r#"
+            // numbers
+            NUMBER = /[0-9]+/;   // digits
+            @priority(1) "if"=>IF;
+            /[a-z]+/ => IDENTIFIER;
+
+
+            /\x20/ => ;
+            keywords IDENTIFIER { "else" => ELSE, "while" => WHILE }
+        "#;
+        let expected = concat!(
+            "// numbers\n",
+            "NUMBER = /[0-9]+/; // digits\n",
+            "@priority(1) \"if\" => IF;\n",
+            "/[a-z]+/          => IDENTIFIER;\n",
+            "\n",
+            "/ / => ;\n",
+            "keywords IDENTIFIER {\n",
+            "    \"else\"  => ELSE,\n",
+            "    \"while\" => WHILE\n",
+            "}\n",
+        );
+        let formatted = format(text)?;
+        assert_eq!(expected, formatted);
+        assert_eq!(formatted, format(&formatted)?);
+        let productions = |text: &str| -> Result<Vec<_>> {
+            let (rules, _) = as_productions::<String>(&parse(text)?)?;
+            Ok(rules.into_iter().map(|rule| (rule.expression().clone(), rule.token_kind().clone(), rule.priority(), rule.name().map(String::from))).collect())
+        };
+        assert_eq!(productions(text)?, productions(&formatted)?);
+        assert!(format("/a/ => A").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use crate::{
+    Error,
+    Lexer,
+    Token,
+};
+
+// Setting this environment variable makes assert_golden bless.
+pub const BLESS_VARIABLE: &str = "SIMPLE_LEXER_BLESS";
+
+// A golden test is a `name.lex` spec with a `name.input` to lex, checked
+// against the tokens expected in `name.tokens`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GoldenTest {
+    name: String,
+    spec: PathBuf,
+    outcome: Outcome,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Passed,
+    // the expected tokens were rewritten
+    Blessed,
+    Failed { expected: String, actual: String },
+    // the test could not be run, such as when the spec has errors
+    Invalid(Error),
+}
+
+impl GoldenTest {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn spec(&self) -> &Path {
+        &self.spec
+    }
+
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+
+    pub fn is_failure(&self) -> bool {
+        match self.outcome {
+            Outcome::Passed | Outcome::Blessed => false,
+            Outcome::Failed { .. } | Outcome::Invalid(_) => true,
+        }
+    }
+}
+
+// Runs every golden test in a directory, in order of name. Blessing writes
+// the tokens produced to the expected files instead of comparing them, so
+// only tests which cannot be run fail.
+pub fn run_golden_tests<P: AsRef<Path>>(directory: P, bless: bool) -> Result<Vec<GoldenTest>, Error> {
+    let directory = directory.as_ref();
+    let entries = fs::read_dir(directory).map_err(|_| Error::new("cannot read directory").in_file(Some(directory)))?;
+    let mut specs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lex"))
+        .collect();
+    specs.sort();
+    Ok(specs.into_iter().map(|spec| {
+        let name = spec.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let outcome = run_golden_test(&spec, bless).unwrap_or_else(Outcome::Invalid);
+        GoldenTest { name, spec, outcome }
+    }).collect())
+}
+
+fn run_golden_test(spec: &Path, bless: bool) -> Result<Outcome, Error> {
+    let input_path = spec.with_extension("input");
+    let tokens_path = spec.with_extension("tokens");
+    let lexer = Lexer::<String>::from_path(spec)?;
+    let input = fs::read_to_string(&input_path).map_err(|_| Error::new("cannot read input").in_file(Some(&input_path)))?;
+    let actual = render_tokens(lexer.lex(&input).as_ref().map(Vec::as_slice));
+    let expected = fs::read_to_string(&tokens_path).ok();
+    if expected.as_ref() == Some(&actual) {
+        return Ok(Outcome::Passed);
+    }
+    if bless {
+        fs::write(&tokens_path, &actual).map_err(|_| Error::new("cannot write expected tokens").in_file(Some(&tokens_path)))?;
+        return Ok(Outcome::Blessed);
+    }
+    match expected {
+        Some(expected) => Ok(Outcome::Failed { expected, actual }),
+        None => Err(Error::new("missing expected tokens").in_file(Some(&tokens_path))),
+    }
+}
+
+// The expected file format, one token per line:
+//
+//     IDENT "x" 1:1-1:2
+//
+// with the text quoted and escaped as a Rust string. A lex error ends the
+// file with a line starting `error: `.
+pub fn render_tokens(tokens: Result<&[Token<String>], &Error>) -> String {
+    match tokens {
+        Ok(tokens) => {
+            let mut rendered = String::new();
+            for token in tokens {
+                let span = token.span();
+                rendered.push_str(&format!("{} {:?} {}:{}-{}:{}\n", token.kind(), token.text(), span.start().line(), span.start().column(), span.end().line(), span.end().column()));
+            }
+            rendered
+        },
+        Err(error) => format!("error: {}\n", error),
+    }
+}
+
+// A line diff of the expected and actual text, with lines only expected
+// marked `-` and lines only produced marked `+`.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    // common[i][j] is the length of the longest common subsequence of the
+    // lines from i and j onwards
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            diff.push_str(&format!(" {}\n", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || common[i + 1][j] >= common[i][j + 1]) {
+            diff.push_str(&format!("-{}\n", expected[i]));
+            i += 1;
+        } else {
+            diff.push_str(&format!("+{}\n", actual[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+// Runs the golden tests in a directory from a test of a downstream crate,
+// panicking with every failure. Tests are blessed when SIMPLE_LEXER_BLESS is
+// set.
+pub fn assert_golden<P: AsRef<Path>>(directory: P) {
+    let bless = env::var_os(BLESS_VARIABLE).is_some();
+    let tests = run_golden_tests(directory, bless).unwrap_or_else(|error| panic!("{}", error));
+    let mut failures = String::new();
+    for test in tests.iter().filter(|test| test.is_failure()) {
+        match test.outcome() {
+            Outcome::Failed { expected, actual } => failures.push_str(&format!("{} failed:\n{}", test.name(), diff(expected, actual))),
+            Outcome::Invalid(error) => failures.push_str(&format!("{} is invalid:\n{}\n", test.name(), error)),
+            Outcome::Passed | Outcome::Blessed => {},
+        }
+    }
+    if !failures.is_empty() {
+        panic!("golden tests failed, set {} to bless them\n{}", BLESS_VARIABLE, failures);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::util::TempDir;
+    use super::{
+        diff,
+        run_golden_tests,
+        Outcome,
+    };
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn golden_tests_bless_pass_and_fail_with_a_diff() -> Result<()> {
+        let temp_dir = TempDir::new("simple-lexer-golden");
+        let directory = temp_dir.path();
+        fs::create_dir_all(directory).map_err(|_| "cannot create directory")?;
+        fs::write(directory.join("words.lex"), "/[a-z]+/ => WORD;\n/ / => ;\n").map_err(|_| "cannot write file")?;
+        fs::write(directory.join("words.input"), "ab c").map_err(|_| "cannot write file")?;
+        fs::write(directory.join("broken.lex"), "/[a-z]+/ => WORD\n").map_err(|_| "cannot write file")?;
+        let outcomes = |bless| -> Result<Vec<(String, Outcome)>> {
+            let tests = run_golden_tests(directory, bless)?;
+            Ok(tests.iter().map(|test| (String::from(test.name()), test.outcome().clone())).collect())
+        };
+        let actual = outcomes(true)?;
+        assert_eq!("broken", actual[0].0);
+        assert!(matches!(actual[0].1, Outcome::Invalid(_)));
+        assert_eq!((String::from("words"), Outcome::Blessed), actual[1]);
+        let expected = "WORD \"ab\" 1:1-1:3\nWORD \"c\" 1:4-1:5\n";
+        assert_eq!(expected, fs::read_to_string(directory.join("words.tokens")).map_err(|_| "cannot read file")?);
+        assert_eq!((String::from("words"), Outcome::Passed), outcomes(false)?[1]);
+        fs::write(directory.join("words.input"), "ab \"").map_err(|_| "cannot write file")?;
+        let actual = String::from("error: 1:4: partial match, found `\"`\n");
+        assert_eq!((String::from("words"), Outcome::Failed { expected: String::from(expected), actual: actual.clone() }), outcomes(false)?[1]);
+        assert_eq!("-WORD \"ab\" 1:1-1:3\n-WORD \"c\" 1:4-1:5\n+error: 1:4: partial match, found `\"`\n", diff(expected, &actual));
+        assert_eq!(" a\n-b\n+c\n d\n", diff("a\nb\nd\n", "a\nc\nd\n"));
+        Ok(())
+    }
+}
@@ -1,9 +1,16 @@
 use std::{
-    collections::BTreeMap as Map,
+    collections::{
+        BTreeMap as Map,
+        BTreeSet as Set,
+    },
+    mem,
+    path::{
+        Path,
+        PathBuf,
+    },
     str::FromStr,
 };
 use lazy_static::lazy_static;
-use segment_map;
 use regular_expression::{
     sym as rsym,
     neg as rneg,
@@ -25,78 +32,636 @@ use simple_parser_bootstrap::{
     ast as past,
     ParseTree,
 };
+use simple_lexer_bootstrap::{
+    Rule,
+    Span,
+};
+use crate::{
+    error::Error,
+    keywords::KeywordTable,
+};
+
+type Result<T> = std::result::Result<T, Error>;
 
-type Result<T> = std::result::Result<T, &'static str>;
+// Characters which must be escaped to appear literally in a spec regular
+// expression.
+const SPECIAL_CHARACTERS: &str = "/|*+?()[]{}^-\\";
 
-#[allow(non_camel_case_types)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum TokenKind {
+    INCLUDE,
+    STRING_LITERAL,
     REGULAR_EXPRESSION,
     PRODUCTION_OPERATOR,
+    DEFINITION_OPERATOR,
     TOKEN_KIND,
     SEMICOLON,
+    ATTRIBUTE,
+    LEFT_PARENTHESIS,
+    RIGHT_PARENTHESIS,
+    INTEGER,
+    KEYWORDS,
+    LEFT_BRACE,
+    RIGHT_BRACE,
+    COMMA,
+    COMMENT,
 }
 use TokenKind::*;
 
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum Nonterminal {
     Root,
+    Include,
+    Definition,
     Production,
     Consumption,
+    LiteralProduction,
+    LiteralConsumption,
+    Attribute,
+    IntegerAttribute,
+    StringAttribute,
+    Keywords,
+    Keyword,
 }
 use Nonterminal::*;
 
-pub fn as_productions<T: FromStr>(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Result<Map<Expression, Option<T>>> {
+// A single statement of a spec along with where it was written.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub path: Option<PathBuf>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StatementKind {
+    Include { path: String },
+    Definition { name: String, regular_expression: String },
+    Production { pattern: Pattern, token_kind: Option<String>, attributes: Attributes },
+    Keywords { token_kind: String, keywords: Vec<(String, String)>, attributes: Attributes },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Pattern {
+    RegularExpression(String),
+    Literal(String),
+}
+
+// The attributes written before a production, such as `@priority(10)`,
+// `@skip`, `@name("identifier")` and `@fragment`, or before a keyword table, such as
+// `@caseless` and `@after("DOT")`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Attributes {
+    pub priority: Option<i64>,
+    pub skip: bool,
+    pub name: Option<String>,
+    pub fragment: bool,
+    pub caseless: bool,
+    pub after: Option<String>,
+}
+
+enum Argument {
+    Integer(i64),
+    String(String),
+}
+
+impl Attributes {
+    fn set(&mut self, name: &str, argument: Option<Argument>) -> Result<()> {
+        match (name, argument) {
+            ("priority", Some(Argument::Integer(priority))) => {
+                if self.priority.replace(priority).is_some() {
+                    return Err(Error::new("duplicate attribute"));
+                }
+            },
+            ("skip", None) => {
+                if self.skip {
+                    return Err(Error::new("duplicate attribute"));
+                }
+                self.skip = true;
+            },
+            ("fragment", None) => {
+                if self.fragment {
+                    return Err(Error::new("duplicate attribute"));
+                }
+                self.fragment = true;
+            },
+            ("name", Some(Argument::String(name))) => {
+                if self.name.replace(name).is_some() {
+                    return Err(Error::new("duplicate attribute"));
+                }
+            },
+            ("caseless", None) => {
+                if self.caseless {
+                    return Err(Error::new("duplicate attribute"));
+                }
+                self.caseless = true;
+            },
+            ("after", Some(Argument::String(after))) => {
+                if self.after.replace(after).is_some() {
+                    return Err(Error::new("duplicate attribute"));
+                }
+            },
+            ("priority", _) | ("skip", _) | ("name", _) | ("fragment", _) | ("caseless", _) | ("after", _) => return Err(Error::new("invalid attribute argument")),
+            _ => return Err(Error::new("unknown attribute")),
+        }
+        Ok(())
+    }
+}
+
+impl Statement {
+    pub(crate) fn error(&self, error: Error) -> Error {
+        error.at(&self.span).in_file(self.path.as_deref())
+    }
+}
+
+pub fn as_statements(parse_tree: &ParseTree<Nonterminal, TokenKind>, path: Option<&Path>) -> Result<Vec<Statement>> {
     if let ParseTree::Nonterminal { nonterminal, children, .. } = parse_tree {
-        match nonterminal {
-            // Root ::= (Production | Consumption)*;
+        let kind = match nonterminal {
+            // Root ::= (Include | Definition | Attribute | IntegerAttribute | StringAttribute | Production | Consumption | LiteralProduction | LiteralConsumption | Keywords)*;
             Root => {
-                let mut productions = Map::new();
+                // attributes apply to the production or keyword table which
+                // follows them
+                let mut statements = Vec::new();
+                let mut attributes = Attributes::default();
+                let mut attributes_span = None;
                 for child in children {
-                    productions.extend(as_productions(child)?);
+                    if let Some((name, argument, span)) = as_attribute(child)? {
+                        attributes.set(&name, argument).map_err(|error| error.at(&span).in_file(path))?;
+                        attributes_span.get_or_insert(span);
+                        continue;
+                    }
+                    for mut statement in as_statements(child, path)? {
+                        if let StatementKind::Production { attributes: statement_attributes, .. } | StatementKind::Keywords { attributes: statement_attributes, .. } = &mut statement.kind {
+                            *statement_attributes = mem::take(&mut attributes);
+                            attributes_span = None;
+                        } else if let Some(span) = attributes_span {
+                            return Err(Error::new("attribute without production").at(&span).in_file(path));
+                        }
+                        statements.push(statement);
+                    }
+                }
+                if let Some(span) = attributes_span {
+                    return Err(Error::new("attribute without production").at(&span).in_file(path));
                 }
-                Ok(productions)
+                return Ok(statements);
+            },
+            // Include ::= INCLUDE STRING_LITERAL SEMICOLON;
+            Include => {
+                StatementKind::Include { path: as_string(&children[1])? }
+            },
+            // Definition ::= TOKEN_KIND DEFINITION_OPERATOR REGULAR_EXPRESSION SEMICOLON;
+            Definition => {
+                StatementKind::Definition { name: as_name(&children[0])?, regular_expression: as_regular_expression(&children[2])? }
             },
             // Production ::= REGULAR_EXPRESSION PRODUCTION_OPERATOR TOKEN_KIND SEMICOLON;
             Production => {
-                Ok(map![as_expression(&children[0])? => Some(as_token_kind(&children[2])?)])
+                StatementKind::Production { pattern: Pattern::RegularExpression(as_regular_expression(&children[0])?), token_kind: Some(as_name(&children[2])?), attributes: Attributes::default() }
             },
             // Consumption ::= REGULAR_EXPRESSION PRODUCTION_OPERATOR SEMICOLON;
             Consumption => {
-                Ok(map![as_expression(&children[0])? => None])
+                StatementKind::Production { pattern: Pattern::RegularExpression(as_regular_expression(&children[0])?), token_kind: None, attributes: Attributes::default() }
+            },
+            // LiteralProduction ::= STRING_LITERAL PRODUCTION_OPERATOR TOKEN_KIND SEMICOLON;
+            LiteralProduction => {
+                StatementKind::Production { pattern: Pattern::Literal(as_string(&children[0])?), token_kind: Some(as_name(&children[2])?), attributes: Attributes::default() }
+            },
+            // LiteralConsumption ::= STRING_LITERAL PRODUCTION_OPERATOR SEMICOLON;
+            LiteralConsumption => {
+                StatementKind::Production { pattern: Pattern::Literal(as_string(&children[0])?), token_kind: None, attributes: Attributes::default() }
+            },
+            // Keywords ::= KEYWORDS TOKEN_KIND LEFT_BRACE Keyword (COMMA Keyword)* RIGHT_BRACE;
+            Keywords => {
+                let mut keywords = Vec::new();
+                for child in children {
+                    // Keyword ::= STRING_LITERAL PRODUCTION_OPERATOR TOKEN_KIND;
+                    if let ParseTree::Nonterminal { nonterminal: Keyword, children, .. } = child {
+                        keywords.push((as_string(&children[0])?, as_name(&children[2])?));
+                    }
+                }
+                StatementKind::Keywords { token_kind: as_name(&children[1])?, keywords, attributes: Attributes::default() }
             },
+            Keyword => {
+                return Err(Error::new("keyword without table").at(&as_span(children)).in_file(path));
+            },
+            Attribute | IntegerAttribute | StringAttribute => {
+                return Err(Error::new("attribute without production").at(&as_span(children)).in_file(path));
+            },
+        };
+        Ok(vec![Statement { kind, path: path.map(Path::to_path_buf), span: as_span(children) }])
+    } else { Err(Error::new("no statements")) }
+}
+
+// Includes must already be resolved, so any remaining include statements are
+// ignored. Productions repeating an earlier pattern with a different token
+// kind are errors, while exact repeats are returned as warnings. Rules are in
+// spec order, with included rules in place of their include.
+pub fn as_productions<T: FromStr>(statements: &[Statement]) -> Result<(Vec<Rule<T>>, Vec<Error>)> {
+    let definitions = as_definitions(statements)?;
+    for statement in statements {
+        match &statement.kind {
+            StatementKind::Definition { regular_expression, .. } => {
+                as_expression(regular_expression, &definitions).map_err(|error| statement.error(error))?;
+            },
+            StatementKind::Production { pattern, attributes, .. } if attributes.fragment => {
+                as_pattern_expression(pattern, &definitions).map_err(|error| statement.error(error))?;
+            },
+            _ => {},
+        }
+    }
+    let mut productions = Vec::new();
+    let mut sources: Map<Expression, (&Statement, Option<&String>)> = Map::new();
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for statement in statements {
+        if let StatementKind::Production { pattern, token_kind, attributes } = &statement.kind {
+            if attributes.caseless || attributes.after.is_some() {
+                return Err(statement.error(Error::new("invalid attribute for production")));
+            }
+            if attributes.fragment {
+                continue;
+            }
+            let expression = as_pattern_expression(pattern, &definitions).map_err(|error| statement.error(error))?;
+            let produced_token_kind = token_kind.as_ref().filter(|_| !attributes.skip);
+            if let Some(&(source, source_token_kind)) = sources.get(&expression) {
+                let note = source.error(Error::new("first production here"));
+                if source_token_kind == produced_token_kind {
+                    warnings.push(statement.error(Error::new("duplicate production")).with_note(note));
+                } else {
+                    errors.push(statement.error(Error::new("conflicting production")).with_note(note));
+                }
+                continue;
+            }
+            sources.insert(expression.clone(), (statement, produced_token_kind));
+            // skipped productions keep their token kind only as a name
+            let rule_token_kind = match token_kind {
+                Some(token_kind) if !attributes.skip => Some(as_token_kind(token_kind).map_err(|error| statement.error(error))?),
+                _ => None,
+            };
+            let mut rule = Rule::new(expression, rule_token_kind)
+                .with_priority(attributes.priority.unwrap_or(0))
+                .with_location(*statement.span.start());
+            if let Some(name) = attributes.name.as_ref().or_else(|| token_kind.as_ref().filter(|_| attributes.skip)) {
+                rule = rule.with_name(name);
+            }
+            productions.push(rule);
         }
-    } else { Err("no productions") }
+    }
+    warnings.extend(unused_definitions(statements, &definitions));
+    match Error::from_errors(errors) {
+        Some(error) => Err(error),
+        None => Ok((productions, warnings)),
+    }
+}
+
+// Definitions and fragments which no production refers to, directly or
+// through other definitions.
+fn unused_definitions(statements: &[Statement], definitions: &Map<String, String>) -> Vec<Error> {
+    let mut pending = Vec::new();
+    for statement in statements {
+        if let StatementKind::Production { pattern: Pattern::RegularExpression(regular_expression), attributes, .. } = &statement.kind {
+            if !attributes.fragment {
+                pending.extend(references(regular_expression));
+            }
+        }
+    }
+    let mut used = Set::new();
+    while let Some(name) = pending.pop() {
+        if let Some(definition) = definitions.get(&name) {
+            if used.insert(name) {
+                pending.extend(references(definition));
+            }
+        }
+    }
+    let mut warnings = Vec::new();
+    for statement in statements {
+        let name = match &statement.kind {
+            StatementKind::Definition { name, .. } => name,
+            StatementKind::Production { token_kind: Some(name), attributes, .. } if attributes.fragment => name,
+            _ => continue,
+        };
+        if !used.contains(name) {
+            warnings.push(statement.error(Error::new("unused definition")));
+        }
+    }
+    warnings
+}
+
+// Keyword tables in spec order. Keywords must be unique within a table,
+// ignoring case if the table is caseless.
+pub fn as_keywords<T: Clone + FromStr + PartialEq>(statements: &[Statement]) -> Result<Vec<KeywordTable<T>>> {
+    let mut tables = Vec::new();
+    for statement in statements {
+        if let StatementKind::Keywords { token_kind, keywords, attributes } = &statement.kind {
+            if attributes.priority.is_some() || attributes.skip || attributes.name.is_some() {
+                return Err(statement.error(Error::new("invalid attribute for keywords")));
+            }
+            let after = match &attributes.after {
+                Some(after) => Some(as_token_kind(after).map_err(|error| statement.error(error))?),
+                None => None,
+            };
+            let mut table = KeywordTable::new(as_token_kind(token_kind).map_err(|error| statement.error(error))?, attributes.caseless, after);
+            for (keyword, keyword_token_kind) in keywords {
+                if keyword.is_empty() {
+                    return Err(statement.error(Error::new("empty literal")));
+                }
+                let keyword_token_kind = as_token_kind(keyword_token_kind).map_err(|error| statement.error(error))?;
+                if !table.insert(keyword, keyword_token_kind) {
+                    return Err(statement.error(Error::new("duplicate keyword").found(keyword)));
+                }
+            }
+            tables.push(table);
+        }
+    }
+    Ok(tables)
+}
+
+// Fragments are productions which only exist to be referenced by other
+// patterns, so they are definitions named by their token kind. They never
+// become rules, so they are neither matched nor accepted on their own.
+pub(crate) fn as_definitions(statements: &[Statement]) -> Result<Map<String, String>> {
+    let mut definitions = Map::new();
+    for statement in statements {
+        let (name, regular_expression) = match &statement.kind {
+            StatementKind::Definition { name, regular_expression } => (name, regular_expression.clone()),
+            StatementKind::Production { pattern, token_kind, attributes } if attributes.fragment => {
+                if attributes.priority.is_some() || attributes.skip || attributes.name.is_some() {
+                    return Err(statement.error(Error::new("invalid attribute for fragment")));
+                }
+                let name = token_kind.as_ref().ok_or_else(|| statement.error(Error::new("fragment without name")))?;
+                match pattern {
+                    Pattern::RegularExpression(regular_expression) => (name, regular_expression.clone()),
+                    Pattern::Literal(literal) if literal.is_empty() => return Err(statement.error(Error::new("empty literal"))),
+                    Pattern::Literal(literal) => (name, escape(literal)),
+                }
+            },
+            _ => continue,
+        };
+        if definitions.insert(name.clone(), regular_expression).is_some() {
+            return Err(statement.error(Error::new("duplicate definition")));
+        }
+    }
+    Ok(definitions)
+}
+
+fn as_pattern_expression(pattern: &Pattern, definitions: &Map<String, String>) -> Result<Expression> {
+    match pattern {
+        Pattern::RegularExpression(regular_expression) => as_expression(regular_expression, definitions),
+        Pattern::Literal(literal) => {
+            if literal.is_empty() {
+                return Err(Error::new("empty literal"));
+            }
+            Ok(Re::new(&escape(literal))?.into_expression())
+        },
+    }
+}
+
+fn as_expression(regular_expression: &str, definitions: &Map<String, String>) -> Result<Expression> {
+    Ok(Re::new(&expand(regular_expression, definitions, &mut Vec::new())?)?.into_expression())
+}
+
+// Escapes every character of a literal as a unicode escape, so the regular
+// expression matches the literal exactly whatever characters it contains.
+pub(crate) fn escape(literal: &str) -> String {
+    literal.chars().map(|character| format!("\\U{:08X}", u32::from(character))).collect()
+}
+
+// Writes a character so a spec regular expression matches it literally, with
+// a short escape where there is one. Digits and commas are unicode escaped
+// too, since after an escaped opening curly bracket they could still read as
+// the bounds of a repetition.
+pub(crate) fn escape_character(character: char) -> String {
+    match character {
+        '\n' => String::from("\\n"),
+        '\r' => String::from("\\r"),
+        '\t' => String::from("\\t"),
+        _ if SPECIAL_CHARACTERS.contains(character) => format!("\\{}", character),
+        _ if character.is_control() || character.is_ascii_digit() || character == ',' => {
+            if u32::from(character) <= 0xFFFF {
+                format!("\\u{:04X}", u32::from(character))
+            } else {
+                format!("\\U{:08X}", u32::from(character))
+            }
+        },
+        _ => character.to_string(),
+    }
+}
+
+fn as_span(children: &[ParseTree<Nonterminal, TokenKind>]) -> Span {
+    let first = children.first().and_then(as_token_span);
+    let last = children.last().and_then(as_token_span);
+    if let (Some(first), Some(last)) = (first, last) {
+        Span::new(*first.start(), *last.end())
+    } else { Span::default() }
+}
+
+fn as_token_span(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Option<Span> {
+    if let ParseTree::Token { token } = parse_tree {
+        Some(*token.span())
+    } else { None }
 }
 
-fn as_expression(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Result<Expression> {
+fn as_regular_expression(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Result<String> {
     if let ParseTree::Token { token } = parse_tree {
-        // /\/([^\/\n\r\\]|\\.)*\// => REGULAR_EXPRESSION;
+        // /\/([^\/\n\r\\]|\\.)+\// => REGULAR_EXPRESSION;
         if let REGULAR_EXPRESSION = token.kind() {
-            Ok(Re::new(&token.text()[1..token.text().len()-1])?.into_expression())
-        } else { Err("not expression") }
-    } else { Err("not expression") }
+            Ok(String::from(&token.text()[1..token.text().len()-1]))
+        } else { Err(Error::new("not expression")) }
+    } else { Err(Error::new("not expression")) }
 }
 
-fn as_token_kind<T: FromStr>(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Result<T> {
+fn as_string(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Result<String> {
+    if let ParseTree::Token { token } = parse_tree {
+        // /"([^"\n\r\\]|\\.)*"/ => STRING_LITERAL;
+        if let STRING_LITERAL = token.kind() {
+            unescape(&token.text()[1..token.text().len()-1]).map_err(|error| error.at(token.span()))
+        } else { Err(Error::new("not string")) }
+    } else { Err(Error::new("not string")) }
+}
+
+// Attribute ::= ATTRIBUTE;
+// IntegerAttribute ::= ATTRIBUTE LEFT_PARENTHESIS INTEGER RIGHT_PARENTHESIS;
+// StringAttribute ::= ATTRIBUTE LEFT_PARENTHESIS STRING_LITERAL RIGHT_PARENTHESIS;
+fn as_attribute(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Result<Option<(String, Option<Argument>, Span)>> {
+    if let ParseTree::Nonterminal { nonterminal, children, .. } = parse_tree {
+        let argument = match nonterminal {
+            Attribute => None,
+            IntegerAttribute => Some(Argument::Integer(as_integer(&children[2])?)),
+            StringAttribute => Some(Argument::String(as_string(&children[2])?)),
+            _ => return Ok(None),
+        };
+        Ok(Some((as_attribute_name(&children[0])?, argument, as_span(children))))
+    } else { Ok(None) }
+}
+
+fn as_attribute_name(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Result<String> {
+    if let ParseTree::Token { token } = parse_tree {
+        // /@[a-z]+/ => ATTRIBUTE;
+        if let ATTRIBUTE = token.kind() {
+            Ok(String::from(&token.text()[1..]))
+        } else { Err(Error::new("not attribute")) }
+    } else { Err(Error::new("not attribute")) }
+}
+
+fn as_integer(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Result<i64> {
+    if let ParseTree::Token { token } = parse_tree {
+        // /-?[0-9]+/ => INTEGER;
+        if let INTEGER = token.kind() {
+            token.text().parse().map_err(|_| Error::new("integer out of range").at(token.span()))
+        } else { Err(Error::new("not integer")) }
+    } else { Err(Error::new("not integer")) }
+}
+
+fn as_name(parse_tree: &ParseTree<Nonterminal, TokenKind>) -> Result<String> {
     if let ParseTree::Token { token } = parse_tree {
         // /[A-Z][0-9A-Z_]*/ => TOKEN_KIND;
         if let TOKEN_KIND = token.kind() {
-            if let Ok(token_kind) = T::from_str(token.text()) {
-                Ok(token_kind)
-            } else { Err("not token kind") }
-        } else { Err("not token kind") }
-    } else { Err("not token kind") }
+            Ok(String::from(token.text()))
+        } else { Err(Error::new("not name")) }
+    } else { Err(Error::new("not name")) }
+}
+
+fn as_token_kind<T: FromStr>(text: &str) -> Result<T> {
+    if let Ok(token_kind) = T::from_str(text) {
+        Ok(token_kind)
+    } else { Err(Error::new("not token kind")) }
+}
+
+fn unescape(text: &str) -> Result<String> {
+    let mut unescaped = String::new();
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        if character == '\\' {
+            unescaped.push(match characters.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('\\') => '\\',
+                Some('"') => '"',
+                _ => return Err(Error::new("invalid escape")),
+            });
+        } else {
+            unescaped.push(character);
+        }
+    }
+    Ok(unescaped)
+}
+
+// The names of the definitions a regular expression refers to, read the same
+// way expand reads them.
+fn references(regular_expression: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut characters = regular_expression.chars().peekable();
+    let mut in_class = false;
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => {
+                characters.next();
+            },
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '{' if !in_class && characters.peek().is_some_and(char::is_ascii_uppercase) => {
+                names.push(characters.by_ref().take_while(|&character| character != '}').collect());
+            },
+            _ => {},
+        }
+    }
+    names
+}
+
+// Replaces each `{NAME}` outside of a character class with the parenthesized
+// expansion of the named definition. Repetitions like `{1,3}` are left alone
+// since definition names must start with an uppercase letter.
+pub(crate) fn expand(regular_expression: &str, definitions: &Map<String, String>, expanding: &mut Vec<String>) -> Result<String> {
+    let mut expanded = String::new();
+    let mut characters = regular_expression.chars().peekable();
+    let mut in_class = false;
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => {
+                expanded.push(character);
+                if let Some(escaped) = characters.next() {
+                    expanded.push(escaped);
+                }
+            },
+            '[' => {
+                in_class = true;
+                expanded.push(character);
+            },
+            ']' => {
+                in_class = false;
+                expanded.push(character);
+            },
+            '{' if !in_class && characters.peek().is_some_and(char::is_ascii_uppercase) => {
+                let mut name = String::new();
+                for character in characters.by_ref() {
+                    match character {
+                        '}' => break,
+                        'A'..='Z' | '0'..='9' | '_' => name.push(character),
+                        _ => return Err(Error::new("invalid definition reference")),
+                    }
+                }
+                let definition = definitions.get(&name).ok_or("undefined definition")?;
+                if expanding.contains(&name) {
+                    return Err(Error::new("recursive definition"));
+                }
+                expanding.push(name);
+                let definition = expand(definition, definitions, expanding)?;
+                expanding.pop();
+                expanded.push('(');
+                expanded.push_str(&definition);
+                expanded.push(')');
+            },
+            _ => expanded.push(character),
+        }
+    }
+    Ok(expanded)
 }
 
 lazy_static! {
+    // /include/ => INCLUDE;
+    // /"([^"\n\r\\]|\\.)*"/ => STRING_LITERAL;
     // /\/([^\/\n\r\\]|\\.)+\// => REGULAR_EXPRESSION;
     // /=>/ => PRODUCTION_OPERATOR;
+    // /=/ => DEFINITION_OPERATOR;
     // /[A-Z][0-9A-Z_]*/ => TOKEN_KIND;
     // /;/ => SEMICOLON;
+    // /@[a-z]+/ => ATTRIBUTE;
+    // /\(/ => LEFT_PARENTHESIS;
+    // /\)/ => RIGHT_PARENTHESIS;
+    // /-?[0-9]+/ => INTEGER;
+    // /keywords/ => KEYWORDS;
+    // /\{/ => LEFT_BRACE;
+    // /\}/ => RIGHT_BRACE;
+    // /,/ => COMMA;
     // /[\n\r\t ]/ => ;
-    // /\/\/[^\n\r]*/ => ;
+    // /\/\/[^\n\r]*/ => COMMENT;
     pub(crate) static ref LEXER_PRODUCTIONS: Map<Expression, Option<TokenKind>> = map![
+        rcon![
+            rsym![rsgl!('i')],
+            rsym![rsgl!('n')],
+            rsym![rsgl!('c')],
+            rsym![rsgl!('l')],
+            rsym![rsgl!('u')],
+            rsym![rsgl!('d')],
+            rsym![rsgl!('e')]
+        ] => Some(INCLUDE),
+        rcon![
+            rsym![rsgl!('"')],
+            rast!(ralt![
+                rneg![
+                    rsgl!('"'),
+                    rsgl!('\n'),
+                    rsgl!('\r'),
+                    rsgl!('\\')
+                ],
+                rcon![
+                    rsym![rsgl!('\\')],
+                    rsym![rall!()]
+                ]
+            ]),
+            rsym![rsgl!('"')]
+        ] => Some(STRING_LITERAL),
         rcon![
             rsym![rsgl!('/')],
             rplu!(ralt![
@@ -117,6 +682,7 @@ lazy_static! {
             rsym![rsgl!('=')],
             rsym![rsgl!('>')]
         ] => Some(PRODUCTION_OPERATOR),
+        rsym![rsgl!('=')] => Some(DEFINITION_OPERATOR),
         rcon![
             rsym![rrng!('A', 'Z')],
             rast!(rsym![
@@ -126,6 +692,32 @@ lazy_static! {
             ])
         ] => Some(TOKEN_KIND),
         rsym![rsgl!(';')] => Some(SEMICOLON),
+        rcon![
+            rsym![rsgl!('@')],
+            rplu!(rsym![rrng!('a', 'z')])
+        ] => Some(ATTRIBUTE),
+        rsym![rsgl!('(')] => Some(LEFT_PARENTHESIS),
+        rsym![rsgl!(')')] => Some(RIGHT_PARENTHESIS),
+        ralt![
+            rplu!(rsym![rrng!('0', '9')]),
+            rcon![
+                rsym![rsgl!('-')],
+                rplu!(rsym![rrng!('0', '9')])
+            ]
+        ] => Some(INTEGER),
+        rcon![
+            rsym![rsgl!('k')],
+            rsym![rsgl!('e')],
+            rsym![rsgl!('y')],
+            rsym![rsgl!('w')],
+            rsym![rsgl!('o')],
+            rsym![rsgl!('r')],
+            rsym![rsgl!('d')],
+            rsym![rsgl!('s')]
+        ] => Some(KEYWORDS),
+        rsym![rsgl!('{')] => Some(LEFT_BRACE),
+        rsym![rsgl!('}')] => Some(RIGHT_BRACE),
+        rsym![rsgl!(',')] => Some(COMMA),
         rsym![
             rsgl!('\n'),
             rsgl!('\r'),
@@ -139,17 +731,45 @@ lazy_static! {
                 rsgl!('\n'),
                 rsgl!('\r')
             ])
-        ] => None
+        ] => Some(COMMENT)
     ];
 
-    // Root ::= (Production | Consumption)*;
+    // Root ::= (Include | Definition | Attribute | IntegerAttribute | StringAttribute | Production | Consumption | LiteralProduction | LiteralConsumption | Keywords)*;
+    // Include ::= INCLUDE STRING_LITERAL SEMICOLON;
+    // Definition ::= TOKEN_KIND DEFINITION_OPERATOR REGULAR_EXPRESSION SEMICOLON;
     // Production ::= REGULAR_EXPRESSION PRODUCTION_OPERATOR TOKEN_KIND SEMICOLON;
     // Consumption ::= REGULAR_EXPRESSION PRODUCTION_OPERATOR SEMICOLON;
+    // LiteralProduction ::= STRING_LITERAL PRODUCTION_OPERATOR TOKEN_KIND SEMICOLON;
+    // LiteralConsumption ::= STRING_LITERAL PRODUCTION_OPERATOR SEMICOLON;
+    // Attribute ::= ATTRIBUTE;
+    // IntegerAttribute ::= ATTRIBUTE LEFT_PARENTHESIS INTEGER RIGHT_PARENTHESIS;
+    // StringAttribute ::= ATTRIBUTE LEFT_PARENTHESIS STRING_LITERAL RIGHT_PARENTHESIS;
+    // Keywords ::= KEYWORDS TOKEN_KIND LEFT_BRACE Keyword (COMMA Keyword)* RIGHT_BRACE;
+    // Keyword ::= STRING_LITERAL PRODUCTION_OPERATOR TOKEN_KIND;
     pub(crate) static ref PARSER_PRODUCTIONS: Map<Nonterminal, simple_parser_bootstrap::Expression<Nonterminal, TokenKind>> = map![
         Root => past!(palt![
+            pnon!(Include),
+            pnon!(Definition),
+            pnon!(Attribute),
+            pnon!(IntegerAttribute),
+            pnon!(StringAttribute),
             pnon!(Production),
-            pnon!(Consumption)
+            pnon!(Consumption),
+            pnon!(LiteralProduction),
+            pnon!(LiteralConsumption),
+            pnon!(Keywords)
         ]),
+        Include => pcon![
+            ptok!(INCLUDE),
+            ptok!(STRING_LITERAL),
+            ptok!(SEMICOLON)
+        ],
+        Definition => pcon![
+            ptok!(TOKEN_KIND),
+            ptok!(DEFINITION_OPERATOR),
+            ptok!(REGULAR_EXPRESSION),
+            ptok!(SEMICOLON)
+        ],
         Production => pcon![
             ptok!(REGULAR_EXPRESSION),
             ptok!(PRODUCTION_OPERATOR),
@@ -160,6 +780,162 @@ lazy_static! {
             ptok!(REGULAR_EXPRESSION),
             ptok!(PRODUCTION_OPERATOR),
             ptok!(SEMICOLON)
+        ],
+        LiteralProduction => pcon![
+            ptok!(STRING_LITERAL),
+            ptok!(PRODUCTION_OPERATOR),
+            ptok!(TOKEN_KIND),
+            ptok!(SEMICOLON)
+        ],
+        LiteralConsumption => pcon![
+            ptok!(STRING_LITERAL),
+            ptok!(PRODUCTION_OPERATOR),
+            ptok!(SEMICOLON)
+        ],
+        Attribute => ptok!(ATTRIBUTE),
+        IntegerAttribute => pcon![
+            ptok!(ATTRIBUTE),
+            ptok!(LEFT_PARENTHESIS),
+            ptok!(INTEGER),
+            ptok!(RIGHT_PARENTHESIS)
+        ],
+        StringAttribute => pcon![
+            ptok!(ATTRIBUTE),
+            ptok!(LEFT_PARENTHESIS),
+            ptok!(STRING_LITERAL),
+            ptok!(RIGHT_PARENTHESIS)
+        ],
+        Keywords => pcon![
+            ptok!(KEYWORDS),
+            ptok!(TOKEN_KIND),
+            ptok!(LEFT_BRACE),
+            pnon!(Keyword),
+            past!(pcon![
+                ptok!(COMMA),
+                pnon!(Keyword)
+            ]),
+            ptok!(RIGHT_BRACE)
+        ],
+        Keyword => pcon![
+            ptok!(STRING_LITERAL),
+            ptok!(PRODUCTION_OPERATOR),
+            ptok!(TOKEN_KIND)
         ]
     ];
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        util::tokens,
+        Lexer,
+    };
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn definitions_expand_in_patterns_and_must_not_recurse() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(r#"
+            DIGIT = /[0-9]/;
+            LETTER = /[a-z]/;
+            /{DIGIT}+/ => NUMBER;
+            /{LETTER}({LETTER}|{DIGIT}){0,3}/ => IDENTIFIER;
+            / / => ;
+        "#)?;
+        assert_eq!(tokens(&[("NUMBER", "42"), ("IDENTIFIER", "a1b2")]), lexer.lex("42 a1b2")?);
+        let recursive = Lexer::<String>::new(r#"
+            A = /a{B}/;
+            B = /b{A}/;
+            /{A}/ => NUMBER;
+        "#);
+        assert_eq!(Some("recursive definition"), recursive.as_ref().err().map(|error| error.message()));
+        Ok(())
+    }
+
+    #[test]
+    fn string_literals_match_their_text_verbatim() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(r#"
+            "=>" => ARROW;
+            "::" => PATH_SEP;
+            "\"" => QUOTE;
+            /[a-z]+/ => IDENTIFIER;
+            " " => ;
+        "#)?;
+        let expected = tokens(&[
+            ("IDENTIFIER", "a"),
+            ("PATH_SEP", "::"),
+            ("IDENTIFIER", "b"),
+            ("ARROW", "=>"),
+            ("QUOTE", "\""),
+        ]);
+        assert_eq!(expected, lexer.lex("a::b => \"")?);
+        Ok(())
+    }
+
+    #[test]
+    fn attributes_set_priority_skip_and_name() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(r#"
+            @name("identifier")
+            /[a-z]+/ => IDENTIFIER;
+            @priority(1) "if" => IF;
+            @skip @name("whitespace") / / => WHITESPACE;
+        "#)?;
+        assert_eq!(tokens(&[("IF", "if"), ("IDENTIFIER", "iff")]), lexer.lex("if iff")?);
+        let invalid = Lexer::<String>::new(r#"
+            @priority("high") "if" => IF;
+        "#);
+        assert_eq!(Some("invalid attribute argument"), invalid.as_ref().err().map(|error| error.message()));
+        let dangling = Lexer::<String>::new(r#"
+            "if" => IF;
+            @skip
+        "#);
+        assert_eq!(Some("attribute without production"), dangling.as_ref().err().map(|error| error.message()));
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_productions_warn_and_conflicting_productions_fail() -> Result<()> {
+        let lexer = Lexer::<String>::new("/a/ => A;\n/b/ => B;\n/a/ => A;\n")?;
+        let warnings = lexer.warnings().iter().map(|warning| (warning.message(), warning.span().map(|span| span.start().line()))).collect::<Vec<_>>();
+        assert_eq!(vec![("duplicate production", Some(3))], warnings);
+        assert_eq!(Some(Some(1)), lexer.warnings()[0].note().map(|note| note.span().map(|span| span.start().line())));
+        let error = Lexer::<String>::new("/a/ => A;\n/a/ => B;\n").err().ok_or("expected error")?;
+        assert_eq!("conflicting production", error.message());
+        assert_eq!(Some(2), error.span().map(|span| span.start().line()));
+        assert_eq!(Some(Some(1)), error.note().map(|note| note.span().map(|span| span.start().line())));
+        Ok(())
+    }
+
+    #[test]
+    fn fragments_are_usable_in_patterns_but_never_emitted() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(r#"
+            @fragment /[0-9]/ => DIGIT;
+            @fragment "0x" => HEX_PREFIX;
+            /{HEX_PREFIX}{DIGIT}+|{DIGIT}+/ => NUMBER;
+            / / => ;
+        "#)?;
+        assert_eq!(tokens(&[("NUMBER", "0x12"), ("NUMBER", "7")]), lexer.lex("0x12 7")?);
+        assert!(lexer.lex("0x").is_err());
+        let unnamed = Lexer::<String>::new(r#"
+            @fragment /[0-9]/ => ;
+        "#);
+        assert_eq!(Some("fragment without name"), unnamed.as_ref().err().map(|error| error.message()));
+        Ok(())
+    }
+
+    #[test]
+    fn unused_definitions_and_fragments_warn() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(concat!(
+            "DIGIT = /[0-9]/;\n",
+            "NUMBER = /{DIGIT}+/;\n",
+            "UNUSED = /x/;\n",
+            "@fragment /[a-z]/ => LETTER;\n",
+            "/{NUMBER}/ => NUMBER;\n",
+            "/if/ => IF;\n",
+            "/[a-z]+/ => IDENT;\n",
+        ))?;
+        let warnings = lexer.warnings().iter().map(|warning| (warning.message(), warning.span().map(|span| span.start().line()))).collect::<Vec<_>>();
+        assert_eq!(vec![("unused definition", Some(3)), ("unused definition", Some(4))], warnings);
+        Ok(())
+    }
+}
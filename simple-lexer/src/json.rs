@@ -0,0 +1,63 @@
+use std::fmt::Display;
+use crate::{
+    Location,
+    Span,
+    Token,
+};
+
+// Quotes text as a JSON string.
+pub fn string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ if character.is_control() => quoted.push_str(&format!("\\u{:04x}", u32::from(character))),
+            _ => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// A token as a single line JSON object, with its kind written as a string.
+// This is the form the serde feature gives a token whose kind serializes as
+// a string.
+//
+//     {"kind":"IDENT","text":"x","span":{"start":{"index":0,"line":1,"column":1},"end":{"index":1,"line":1,"column":2}}}
+pub fn token<T: Display>(token: &Token<T>) -> String {
+    format!("{{\"kind\":{},\"text\":{},\"span\":{}}}", string(&token.kind().to_string()), string(token.text()), span(token.span()))
+}
+
+fn span(span: &Span) -> String {
+    format!("{{\"start\":{},\"end\":{}}}", location(span.start()), location(span.end()))
+}
+
+fn location(location: &Location) -> String {
+    format!("{{\"index\":{},\"line\":{},\"column\":{}}}", location.index(), location.line(), location.column())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Lexer;
+    use super::token as to_json;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn tokens_serialize_with_kind_text_and_span() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new("/[a-z]+/ => WORD;\n/ / => ;\n")?;
+        let tokens = lexer.lex("a \"b");
+        assert_eq!(Some("partial match"), tokens.as_ref().err().map(|error| error.message()));
+        let tokens = lexer.lex("a bc")?;
+        assert_eq!(concat!(
+            "{\"kind\":\"WORD\",\"text\":\"bc\",\"span\":",
+            "{\"start\":{\"index\":2,\"line\":1,\"column\":3},",
+            "\"end\":{\"index\":4,\"line\":1,\"column\":5}}}"
+        ), to_json(&tokens[1]));
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use crate::Token;
+
+// Reclassifies tokens of one kind, such as identifiers, whose text is a
+// keyword. Keeping keywords out of the automaton avoids a rule per keyword
+// and the priority conflicts those rules have with the identifier rule.
+#[derive(Clone, Debug)]
+pub(crate) struct KeywordTable<T> {
+    token_kind: T,
+    keywords: HashMap<String, T>,
+    caseless: bool,
+    after: Option<T>,
+}
+
+impl<T: Clone + PartialEq> KeywordTable<T> {
+    // A caseless table matches keywords regardless of case. A table with an
+    // `after` kind holds contextual keywords, which are only reclassified
+    // directly after a token of that kind.
+    pub(crate) fn new(token_kind: T, caseless: bool, after: Option<T>) -> KeywordTable<T> {
+        KeywordTable { token_kind, keywords: HashMap::new(), caseless, after }
+    }
+
+    // Returns false if the keyword is already in the table.
+    pub(crate) fn insert(&mut self, keyword: &str, token_kind: T) -> bool {
+        self.keywords.insert(self.key(keyword), token_kind).is_none()
+    }
+
+    pub(crate) fn keywords(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.keywords.iter().map(|(keyword, token_kind)| (keyword.as_str(), token_kind))
+    }
+
+    pub(crate) fn reclassify(&self, tokens: &mut [Token<T>]) {
+        for index in 0..tokens.len() {
+            if *tokens[index].kind() != self.token_kind {
+                continue;
+            }
+            if let Some(after) = &self.after {
+                if index == 0 || tokens[index - 1].kind() != after {
+                    continue;
+                }
+            }
+            if let Some(token_kind) = self.keywords.get(&self.key(tokens[index].text())) {
+                tokens[index] = Token::with_span(token_kind.clone(), tokens[index].text(), *tokens[index].span());
+            }
+        }
+    }
+
+    fn key(&self, keyword: &str) -> String {
+        if self.caseless {
+            keyword.to_lowercase()
+        } else {
+            String::from(keyword)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        util::tokens,
+        Lexer,
+    };
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn keywords_reclassify_tokens_by_case_and_preceding_kind() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(r#"
+            /[A-Za-z]+/ => IDENTIFIER;
+            "." => DOT;
+            / / => ;
+            @caseless
+            keywords IDENTIFIER { "if" => IF, "else" => ELSE }
+            @after("DOT")
+            keywords IDENTIFIER { "get" => GET }
+        "#)?;
+        let expected = tokens(&[
+            ("IF", "If"),
+            ("IDENTIFIER", "get"),
+            ("DOT", "."),
+            ("GET", "get"),
+            ("ELSE", "ELSE"),
+            ("IDENTIFIER", "elsewhere"),
+        ]);
+        assert_eq!(expected, lexer.lex("If get.get ELSE elsewhere")?);
+        let duplicate = Lexer::<String>::new(r#"
+            @caseless keywords IDENTIFIER { "if" => IF, "IF" => IF }
+        "#);
+        assert_eq!(Some("duplicate keyword"), duplicate.as_ref().err().map(|error| error.message()));
+        Ok(())
+    }
+}
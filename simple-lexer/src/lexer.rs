@@ -1,45 +1,210 @@
-use std::str::FromStr;
-use simple_lexer_bootstrap::Lexer as LexerBootstrap;
+use std::{
+    fmt::Debug,
+    path::Path,
+    str::FromStr,
+};
+use simple_lexer_bootstrap::{
+    Lexer as LexerBootstrap,
+    Error as ErrorBootstrap,
+    Analysis,
+    DotOptions,
+    Rule,
+    SampleOptions,
+    SeededRng,
+    Step,
+};
 use crate::{
     grammar::{
-        LEXER_PRODUCTIONS,
-        PARSER_PRODUCTIONS,
-        Nonterminal,
+        Statement,
         as_productions,
+        as_keywords,
+    },
+    keywords::KeywordTable,
+    spec::{
+        parse,
+        load,
     },
+    flex::import_flex,
+    Error,
     Token,
 };
-use simple_parser_bootstrap::Parser;
 
-type Result<T> = std::result::Result<T, &'static str>;
+// The tokens lexed from some text, or where lexing it failed.
+type Lexed<T> = std::result::Result<Vec<Token<T>>, Error>;
 
 pub struct Lexer<T> {
-    lexer: LexerBootstrap<T>
+    lexer: LexerBootstrap<T>,
+    keywords: Vec<KeywordTable<T>>,
+    warnings: Vec<Error>,
 }
 
 impl<T: Clone + FromStr + Ord> Lexer<T> {
-    pub fn new(productions: &str) -> Result<Lexer<T>> {
-        let lexer = LexerBootstrap::new(LEXER_PRODUCTIONS.clone());
-        let parser = Parser::new(PARSER_PRODUCTIONS.clone(), Nonterminal::Root);
-        let tokens = lexer.lex(productions)?;
-        let parse_tree = parser.parse(&tokens).unwrap();
-        let productions = as_productions(&parse_tree)?;
-        Ok(Lexer { lexer: LexerBootstrap::new(productions) })
+    pub fn new(productions: &str) -> std::result::Result<Lexer<T>, Error> {
+        Lexer::from_statements(&parse(productions)?)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> std::result::Result<Lexer<T>, Error> {
+        Lexer::from_statements(&load(path.as_ref())?)
+    }
+
+    // Imports a flex scanner. Whatever could not be imported is reported in
+    // the warnings, ahead of the warnings for the imported spec.
+    pub fn from_flex(text: &str) -> std::result::Result<Lexer<T>, Error> {
+        let (spec, reports) = import_flex(text)?;
+        let mut lexer = Lexer::new(&spec)?;
+        lexer.warnings = reports.into_iter().chain(lexer.warnings).collect();
+        Ok(lexer)
+    }
+
+    fn from_statements(statements: &[Statement]) -> std::result::Result<Lexer<T>, Error> {
+        let (rules, warnings) = as_productions(statements)?;
+        let keywords = as_keywords(statements)?;
+        Ok(Lexer { lexer: LexerBootstrap::from_rules(rules)?, keywords, warnings })
+    }
+}
+
+impl<T: Clone + Ord> Lexer<T> {
+    pub(crate) fn from_rules(rules: Vec<Rule<T>>) -> std::result::Result<Lexer<T>, &'static str> {
+        Ok(Lexer { lexer: LexerBootstrap::from_rules(rules)?, keywords: Vec::new(), warnings: Vec::new() })
+    }
+
+    // Problems in the spec which did not stop the lexer from being built,
+    // such as a production repeated exactly.
+    pub fn warnings(&self) -> &[Error] {
+        &self.warnings
+    }
+
+    pub fn rules(&self) -> &[Rule<T>] {
+        self.lexer.rules()
+    }
+
+    // Static analysis of the productions. Keyword tables are not considered.
+    pub fn analyze(&self) -> Analysis<T> {
+        self.lexer.analyze()
+    }
+
+    // Keyword tables are applied in spec order once the whole text is lexed.
+    pub fn lex(&self, text: &str) -> Lexed<T> {
+        self.finish(text, self.lexer.lex(text))
+    }
+
+    // Lexes the text like lex, also returning every DFA transition taken.
+    // Keyword tables only change the tokens, never the steps.
+    pub fn trace(&self, text: &str) -> (Vec<Step<T>>, Lexed<T>) {
+        let (steps, tokens) = self.lexer.trace(text);
+        (steps, self.finish(text, tokens))
+    }
+
+    // Random text which lexes to the returned tokens. Weights apply to the
+    // token kinds of productions, before keyword tables reclassify tokens.
+    pub fn sample(&self, rng: &mut SeededRng, options: &SampleOptions<T>) -> (String, Vec<Token<T>>) {
+        let (text, mut tokens) = self.lexer.sample(rng, options);
+        for keywords in &self.keywords {
+            keywords.reclassify(&mut tokens);
+        }
+        (text, tokens)
     }
 
-    pub fn lex(&self, text: &str) -> Result<Vec<Token<T>>> {
-        self.lexer.lex(text)
+    // The shortest string each production produces a token for, or None for
+    // productions which never win, in spec order. Examples a keyword table
+    // would reclassify are passed over for the next shortest.
+    pub fn examples(&self) -> Vec<Option<String>> {
+        self.examples_up_to(1).into_iter().map(|rule_examples| rule_examples.into_iter().next()).collect()
+    }
+
+    // Up to count short strings for each production, shortest first. The
+    // search widens until count examples are left after keyword tables pass
+    // over theirs, or the production has no more.
+    pub fn examples_up_to(&self, count: usize) -> Vec<Vec<String>> {
+        let rules = self.lexer.rules();
+        let mut candidates = count.max(1);
+        loop {
+            let candidate_examples = self.lexer.examples_up_to(candidates);
+            let exhausted = candidate_examples.iter().map(|rule_examples| rule_examples.len() < candidates).collect::<Vec<_>>();
+            let examples = candidate_examples.into_iter().zip(rules).map(|(rule_examples, rule)| {
+                rule_examples.into_iter().filter(|example| self.keeps_kind(rule, example)).take(count).collect::<Vec<_>>()
+            }).collect::<Vec<_>>();
+            if examples.iter().zip(exhausted).all(|(rule_examples, exhausted)| exhausted || rule_examples.len() == count) {
+                return examples;
+            }
+            candidates *= 2;
+        }
+    }
+
+    // Every keyword with the kind it is reclassified to, by kind and then
+    // shortest first. Keywords never come from the automaton, so they have
+    // no production to be examples of. Caseless keywords are in lowercase,
+    // and contextual keywords only take their kind after a token of the
+    // kind their table names.
+    pub fn keyword_examples(&self) -> Vec<(T, String)> {
+        let mut examples = self.keywords.iter().flat_map(KeywordTable::keywords).map(|(keyword, token_kind)| (token_kind.clone(), String::from(keyword))).collect::<Vec<_>>();
+        examples.sort_by(|(kind, keyword), (other_kind, other_keyword)| (kind, keyword.len(), keyword).cmp(&(other_kind, other_keyword.len(), other_keyword)));
+        examples.dedup();
+        examples
+    }
+
+    // Whether text the production matches on its own keeps the production's
+    // token kind once keyword tables are applied.
+    fn keeps_kind(&self, rule: &Rule<T>, text: &str) -> bool {
+        let token_kind = match rule.token_kind() {
+            Some(token_kind) => token_kind,
+            None => return true,
+        };
+        let mut tokens = vec![Token::new(token_kind.clone(), text)];
+        for keywords in &self.keywords {
+            keywords.reclassify(&mut tokens);
+        }
+        tokens[0].kind() == token_kind
+    }
+
+    fn finish(&self, text: &str, tokens: std::result::Result<Vec<Token<T>>, ErrorBootstrap>) -> Lexed<T> {
+        let mut tokens = tokens.map_err(|error| {
+            let span = error.span();
+            Error::new(String::from(error.message())).at(span).found(&text[span.start().index()..span.end().index()])
+        })?;
+        for keywords in &self.keywords {
+            keywords.reclassify(&mut tokens);
+        }
+        Ok(tokens)
+    }
+}
+
+// Keyword tables are applied after the automaton, so they do not appear in
+// its graphs.
+impl<T: Clone + Debug + Ord> Lexer<T> {
+    // The minimized DFA of every mode in Graphviz DOT.
+    pub fn to_dot(&self) -> String {
+        self.lexer.to_dot()
+    }
+
+    pub fn to_dot_with(&self, options: &DotOptions) -> String {
+        self.lexer.to_dot_with(options)
+    }
+
+    // The NFA of every mode, before determinization, in Graphviz DOT.
+    pub fn nfa_to_dot(&self) -> String {
+        self.lexer.nfa_to_dot()
+    }
+
+    pub fn nfa_to_dot_with(&self, options: &DotOptions) -> String {
+        self.lexer.nfa_to_dot_with(options)
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::upper_case_acronyms)]
 mod tests {
     use std::str::FromStr;
     use crate::{
+        util::tokens,
+        DotOptions,
+        SampleOptions,
+        SeededRng,
         Lexer,
         Token,
     };
-    use super::Result;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
     #[test]
     fn test_1() -> Result<()> {
@@ -47,10 +212,10 @@ mod tests {
         enum TokenKind {
             A,
             B,
-        };
+        }
         impl FromStr for TokenKind {
             type Err = &'static str;
-            fn from_str(text: &str) -> Result<Self> {
+            fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
                 use TokenKind::*;
                 match text {
                     "A" => Ok(A),
@@ -82,10 +247,10 @@ mod tests {
         enum TokenKind {
             A_REP,
             B_REP
-        };
+        }
         impl FromStr for TokenKind {
             type Err = &'static str;
-            fn from_str(text: &str) -> Result<Self> {
+            fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
                 use TokenKind::*;
                 match text {
                     "A_REP" => Ok(A_REP),
@@ -119,10 +284,10 @@ mod tests {
             AB,
             BB,
             B,
-        };
+        }
         impl FromStr for TokenKind {
             type Err = &'static str;
-            fn from_str(text: &str) -> Result<Self> {
+            fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
                 use TokenKind::*;
                 match text {
                     "A" => Ok(A),
@@ -177,7 +342,7 @@ mod tests {
         }
         impl FromStr for TokenKind {
             type Err = &'static str;
-            fn from_str(text: &str) -> Result<Self> {
+            fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
                 use TokenKind::*;
                 match text {
                     "VERTICAL_BAR" => Ok(VERTICAL_BAR),
@@ -246,4 +411,85 @@ mod tests {
         assert_eq!(expected, actual);
         Ok(())
     }
+
+    #[test]
+    fn literals_win_ties_with_later_patterns() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(r#"
+            "if" => IF;
+            /[a-z]+/ => IDENTIFIER;
+            / / => ;
+        "#)?;
+        assert_eq!(tokens(&[("IF", "if"), ("IDENTIFIER", "iff")]), lexer.lex("if iff")?);
+        Ok(())
+    }
+
+    #[test]
+    fn lex_errors_point_at_the_unmatched_character() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new("/a/ => A;")?;
+        let error = lexer.lex("ab").err().ok_or("expected error")?;
+        assert_eq!(Some((1, 2)), error.span().map(|span| (span.start().line(), span.start().column())));
+        Ok(())
+    }
+
+    #[test]
+    fn analysis_reports_rules_shadowed_by_earlier_ones() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new("/if/ => IF;\n/[a-z]+/ => IDENT;\n")?;
+        let analysis = lexer.analyze();
+        let ambiguous_rules = analysis.ambiguous_rules().iter().map(|(winner, loser)| (winner.token_kind().clone(), loser.token_kind().clone())).collect::<Vec<_>>();
+        assert_eq!(vec![(Some(String::from("IF")), Some(String::from("IDENT")))], ambiguous_rules);
+        assert!(analysis.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn samples_lex_to_their_own_tokens() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(concat!(
+            "/[a-z]+/ => IDENT;\n",
+            "/[0-9]+/ => NUMBER;\n",
+            "/ +/ => ;\n",
+            "keywords IDENT { \"if\" => IF, \"else\" => ELSE }\n",
+        ))?;
+        let options = SampleOptions::new().with_max_tokens(20);
+        for seed in 0..16 {
+            let (text, tokens) = lexer.sample(&mut SeededRng::new(seed), &options);
+            assert_eq!(lexer.lex(&text)?, tokens);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn examples_are_the_shortest_strings_each_rule_wins() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(concat!(
+            "/i[a-z]*/ => IDENT;\n",
+            "/[0-9]+/ => NUMBER;\n",
+            "/ +/ => ;\n",
+            "keywords IDENT { \"i\" => I }\n",
+        ))?;
+        let expected = vec![Some(String::from("ia")), Some(String::from("0")), Some(String::from(" "))];
+        assert_eq!(expected, lexer.examples());
+        assert_eq!(vec![String::from("0"), String::from("00")], lexer.examples_up_to(2)[1]);
+        assert_eq!(vec![String::from("ia"), String::from("iaa"), String::from("iaaa")], lexer.examples_up_to(3)[0]);
+        assert_eq!(vec![(String::from("I"), String::from("i"))], lexer.keyword_examples());
+        Ok(())
+    }
+
+    #[test]
+    fn dot_graphs_of_a_spec_show_its_productions() -> Result<()> {
+        let lexer: Lexer<String> = Lexer::new(r#"
+            "if" => IF;
+            @name("identifier") /[a-z]+/ => IDENTIFIER;
+            / / => ;
+            keywords IDENTIFIER { "else" => ELSE }
+        "#)?;
+        let dot = lexer.to_dot();
+        assert!(dot.starts_with("digraph dfa {\n"));
+        assert!(dot.contains("\\n\\\"IF\\\"\\nidentifier\", shape=doublecircle"));
+        assert!(dot.contains("[label=\"f\"]"));
+        assert!(!dot.contains("ELSE"));
+        assert_eq!(dot, lexer.to_dot_with(&DotOptions::new()));
+        let nfa = lexer.nfa_to_dot();
+        assert!(nfa.starts_with("digraph nfa {\n"));
+        assert_eq!(nfa, lexer.nfa_to_dot_with(&DotOptions::new()));
+        Ok(())
+    }
 }
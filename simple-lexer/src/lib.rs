@@ -2,6 +2,54 @@
 mod util;
 mod lexer;
 mod grammar;
+mod error;
+mod spec;
+mod syntax;
+mod builder;
+mod keywords;
+mod format;
+mod json;
+mod textmate;
+mod flex;
+mod golden;
 
-pub use crate::lexer::Lexer;
-pub use simple_lexer_bootstrap::Token;
+pub use crate::{
+    lexer::Lexer,
+    error::{
+        Error,
+        BuildError,
+    },
+    builder::{
+        LexerBuilder,
+        IntoExpression,
+    },
+    format::format,
+    textmate::to_textmate,
+    flex::import_flex,
+    golden::{
+        GoldenTest,
+        Outcome,
+        BLESS_VARIABLE,
+        run_golden_tests,
+        render_tokens,
+        diff,
+        assert_golden,
+    },
+    json::{
+        string as to_json_string,
+        token as to_json,
+    },
+};
+pub use simple_lexer_bootstrap::{
+    Analysis,
+    DotOptions,
+    ShadowedRule,
+    Rule,
+    Step,
+    SampleOptions,
+    SeededRng,
+    Token,
+    Span,
+    Location,
+    DEFAULT_MODE,
+};
@@ -0,0 +1,173 @@
+use std::{
+    cmp::max,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use simple_lexer_bootstrap::{
+    Lexer as LexerBootstrap,
+    Token,
+};
+use simple_parser_bootstrap::Parser;
+use crate::{
+    error::Error,
+    grammar::{
+        LEXER_PRODUCTIONS,
+        PARSER_PRODUCTIONS,
+        Nonterminal,
+        Statement,
+        StatementKind,
+        TokenKind::{
+            self,
+            COMMENT,
+        },
+        as_statements,
+    },
+    syntax::{
+        check,
+        statements,
+    },
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+// Parses spec text, resolving includes relative to the current directory.
+pub fn parse(text: &str) -> Result<Vec<Statement>> {
+    parse_with(text, None, &mut Vec::new())
+}
+
+// Loads a spec file, resolving includes relative to the including file.
+pub fn load(path: &Path) -> Result<Vec<Statement>> {
+    let canonical_path = fs::canonicalize(path).map_err(|_| Error::new("cannot read file").in_file(Some(path)))?;
+    let text = fs::read_to_string(path).map_err(|_| Error::new("cannot read file").in_file(Some(path)))?;
+    parse_with(&text, Some(path), &mut vec![canonical_path])
+}
+
+// The including stack holds the canonical paths of every file currently being
+// parsed, so an include of any of them is a cycle.
+fn parse_with(text: &str, path: Option<&Path>, including: &mut Vec<PathBuf>) -> Result<Vec<Statement>> {
+    let lexer = LexerBootstrap::new(LEXER_PRODUCTIONS.clone());
+    let parser = Parser::new(PARSER_PRODUCTIONS.clone(), Nonterminal::Root);
+    let (tokens, mut errors) = lex(&lexer, text);
+    // comments are only kept for the formatter
+    let tokens: Vec<Token<TokenKind>> = tokens.into_iter().filter(|token| *token.kind() != COMMENT).collect();
+    errors.extend(check(&tokens));
+    if let Some(error) = Error::from_errors(errors) {
+        return Err(error.in_file(path));
+    }
+    let parse_tree = parser.parse(&tokens).map_err(|_| invalid_spec(&parser, &tokens).in_file(path))?;
+    let mut statements = Vec::new();
+    for statement in as_statements(&parse_tree, path)? {
+        if let StatementKind::Include { path: include_path } = &statement.kind {
+            let include_error = |message: &'static str| Error::new(message).at(&statement.span).in_file(path);
+            let include_path = path.and_then(Path::parent).unwrap_or_else(|| Path::new("")).join(include_path);
+            let canonical_path = fs::canonicalize(&include_path).map_err(|_| include_error("cannot read include"))?;
+            if including.contains(&canonical_path) {
+                return Err(include_error("include cycle"));
+            }
+            let text = fs::read_to_string(&include_path).map_err(|_| include_error("cannot read include"))?;
+            including.push(canonical_path);
+            let included_statements = parse_with(&text, Some(&include_path), including);
+            including.pop();
+            statements.extend(included_statements?);
+        } else {
+            statements.push(statement);
+        }
+    }
+    Ok(statements)
+}
+
+// The parser does not say where it failed, so the statements are parsed one
+// at a time and the first one it rejects is reported.
+fn invalid_spec(parser: &Parser<Nonterminal, TokenKind>, tokens: &[Token<TokenKind>]) -> Error {
+    let error = Error::new("invalid spec");
+    match statements(tokens).into_iter().find(|statement| parser.parse(&tokens[statement.clone()]).is_err()) {
+        Some(statement) => {
+            let first = &tokens[statement.start];
+            error.at(first.span()).found(first.text())
+        },
+        None => error,
+    }
+}
+
+// Lexes spec text, reporting each invalid token. After an error the rest of
+// its line is blanked out and lexing starts over, which keeps the locations
+// of every other token intact.
+pub(crate) fn lex(lexer: &LexerBootstrap<TokenKind>, text: &str) -> (Vec<Token<TokenKind>>, Vec<Error>) {
+    let mut text = String::from(text);
+    let mut errors = Vec::new();
+    loop {
+        match lexer.lex(&text) {
+            Ok(tokens) => return (tokens, errors),
+            Err(error) => {
+                let start = error.span().start().index();
+                if start >= text.len() {
+                    return (Vec::new(), errors);
+                }
+                let found = text[start..error.span().end().index()].trim_end();
+                errors.push(Error::new("invalid token").at(error.span()).found(found));
+                let line_end = text[start..].find('\n').map_or(text.len(), |offset| start + offset);
+                let end = max(line_end, start + text[start..].chars().next().map_or(0, char::len_utf8));
+                let blank = " ".repeat(end - start);
+                text.replace_range(start..end, &blank);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use simple_lexer_bootstrap::Lexer as LexerBootstrap;
+    use simple_parser_bootstrap::Parser;
+    use crate::{
+        grammar::{
+            LEXER_PRODUCTIONS,
+            PARSER_PRODUCTIONS,
+            Nonterminal,
+        },
+        util::{
+            tokens,
+            TempDir,
+        },
+        Lexer,
+    };
+    use super::{
+        invalid_spec,
+        lex,
+    };
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn includes_resolve_relative_to_the_including_file_and_must_not_cycle() -> Result<()> {
+        let temp_dir = TempDir::new("simple-lexer-includes");
+        let directory = temp_dir.path();
+        fs::create_dir_all(directory.join("common")).map_err(|_| "cannot create directory")?;
+        fs::write(directory.join("common").join("digits.lex"), "DIGIT = /[0-9]/;\n/ / => ;\n").map_err(|_| "cannot write file")?;
+        fs::write(directory.join("main.lex"), "include \"common/digits.lex\";\n/{DIGIT}+/ => NUMBER;\n").map_err(|_| "cannot write file")?;
+        fs::write(directory.join("a.lex"), "include \"b.lex\";\n").map_err(|_| "cannot write file")?;
+        fs::write(directory.join("b.lex"), "/ / => ;\ninclude \"a.lex\";\n").map_err(|_| "cannot write file")?;
+        let lexer: Lexer<String> = Lexer::from_path(directory.join("main.lex"))?;
+        assert_eq!(tokens(&[("NUMBER", "12"), ("NUMBER", "345")]), lexer.lex("12 345")?);
+        let error = Lexer::<String>::from_path(directory.join("a.lex")).err().ok_or("no error")?;
+        assert_eq!("include cycle", error.message());
+        assert_eq!(Some(directory.join("b.lex").as_path()), error.path());
+        assert_eq!(Some(2), error.span().map(|span| span.start().line()));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_specs_point_at_the_first_statement_the_parser_rejects() -> Result<()> {
+        let lexer = LexerBootstrap::new(LEXER_PRODUCTIONS.clone());
+        let parser = Parser::new(PARSER_PRODUCTIONS.clone(), Nonterminal::Root);
+        let (tokens, _) = lex(&lexer, "/a/ => A;\n/b/ A;\n/ / => ;\n");
+        let error = invalid_spec(&parser, &tokens);
+        assert_eq!("invalid spec", error.message());
+        assert_eq!(Some((2, 1)), error.span().map(|span| (span.start().line(), span.start().column())));
+        assert_eq!(Some("/b/"), error.text());
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use std::ops::Range;
+use simple_lexer_bootstrap::{
+    Token,
+    Span,
+};
+use crate::{
+    error::Error,
+    grammar::TokenKind::{
+        self,
+        *,
+    },
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+// Checks the statements of a spec before it is parsed, so that each mistake
+// is reported with what was expected instead of failing the whole parse.
+// After an error, checking resumes at the next statement.
+pub fn check(tokens: &[Token<TokenKind>]) -> Vec<Error> {
+    let mut errors = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        if let Err(error) = check_statement(tokens, &mut index) {
+            errors.push(error);
+            recover(tokens, &mut index);
+        }
+    }
+    errors
+}
+
+// The token ranges of the statements, split the same way check reads them.
+pub fn statements(tokens: &[Token<TokenKind>]) -> Vec<Range<usize>> {
+    let mut statements = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        let start = index;
+        if check_statement(tokens, &mut index).is_err() {
+            recover(tokens, &mut index);
+        }
+        statements.push(start..index);
+    }
+    statements
+}
+
+fn check_statement(tokens: &[Token<TokenKind>], index: &mut usize) -> Result<()> {
+    let first = &tokens[*index];
+    *index += 1;
+    match first.kind() {
+        // Include ::= INCLUDE STRING_LITERAL SEMICOLON;
+        INCLUDE => {
+            expect(tokens, index, &[STRING_LITERAL], "expected path after `include`")?;
+            expect(tokens, index, &[SEMICOLON], "expected `;` after include path")?;
+        },
+        // Definition ::= TOKEN_KIND DEFINITION_OPERATOR REGULAR_EXPRESSION SEMICOLON;
+        TOKEN_KIND => {
+            expect(tokens, index, &[DEFINITION_OPERATOR], "expected `=` after definition name")?;
+            expect(tokens, index, &[REGULAR_EXPRESSION], "expected regular expression after `=`")?;
+            expect(tokens, index, &[SEMICOLON], "expected `;` after definition")?;
+        },
+        // Attribute ::= ATTRIBUTE;
+        // IntegerAttribute ::= ATTRIBUTE LEFT_PARENTHESIS INTEGER RIGHT_PARENTHESIS;
+        // StringAttribute ::= ATTRIBUTE LEFT_PARENTHESIS STRING_LITERAL RIGHT_PARENTHESIS;
+        ATTRIBUTE => {
+            if tokens.get(*index).map(Token::kind) == Some(&LEFT_PARENTHESIS) {
+                *index += 1;
+                expect(tokens, index, &[INTEGER, STRING_LITERAL], "expected integer or string as attribute argument")?;
+                expect(tokens, index, &[RIGHT_PARENTHESIS], "expected `)` after attribute argument")?;
+            }
+        },
+        // Keywords ::= KEYWORDS TOKEN_KIND LEFT_BRACE Keyword (COMMA Keyword)* RIGHT_BRACE;
+        // Keyword ::= STRING_LITERAL PRODUCTION_OPERATOR TOKEN_KIND;
+        KEYWORDS => {
+            expect(tokens, index, &[TOKEN_KIND], "expected token kind after `keywords`")?;
+            expect(tokens, index, &[LEFT_BRACE], "expected `{` after token kind")?;
+            loop {
+                expect(tokens, index, &[STRING_LITERAL], "expected keyword string")?;
+                expect(tokens, index, &[PRODUCTION_OPERATOR], "expected `=>` after keyword")?;
+                expect(tokens, index, &[TOKEN_KIND], "expected token kind after `=>`")?;
+                if expect(tokens, index, &[COMMA, RIGHT_BRACE], "expected `,` or `}` after keyword")? == RIGHT_BRACE {
+                    break;
+                }
+            }
+        },
+        // Production ::= REGULAR_EXPRESSION PRODUCTION_OPERATOR TOKEN_KIND SEMICOLON;
+        // Consumption ::= REGULAR_EXPRESSION PRODUCTION_OPERATOR SEMICOLON;
+        // LiteralProduction ::= STRING_LITERAL PRODUCTION_OPERATOR TOKEN_KIND SEMICOLON;
+        // LiteralConsumption ::= STRING_LITERAL PRODUCTION_OPERATOR SEMICOLON;
+        REGULAR_EXPRESSION | STRING_LITERAL => {
+            expect(tokens, index, &[PRODUCTION_OPERATOR], "expected `=>` after pattern")?;
+            if expect(tokens, index, &[TOKEN_KIND, SEMICOLON], "expected token kind or `;` after `=>`")? == TOKEN_KIND {
+                expect(tokens, index, &[SEMICOLON], "expected `;` after token kind")?;
+            }
+        },
+        _ => {
+            *index -= 1;
+            return Err(Error::new("expected statement").at(first.span()).found(first.text()));
+        },
+    }
+    Ok(())
+}
+
+fn expect(tokens: &[Token<TokenKind>], index: &mut usize, token_kinds: &[TokenKind], message: &'static str) -> Result<TokenKind> {
+    match tokens.get(*index) {
+        Some(token) if token_kinds.contains(token.kind()) => {
+            *index += 1;
+            Ok(*token.kind())
+        },
+        Some(token) => Err(Error::new(message).at(token.span()).found(token.text())),
+        None => {
+            // report missing tokens at the end of the last token
+            let end = *tokens[tokens.len() - 1].span().end();
+            Err(Error::new(message).at(&Span::new(end, end)))
+        },
+    }
+}
+
+// Skips from the offending token to the next token which can start a
+// statement, or past the next semicolon or closing brace, whichever comes
+// first. An offending token on the same line as the token before it most
+// likely belongs to the broken statement, so it is skipped even if it could
+// start a statement.
+fn recover(tokens: &[Token<TokenKind>], index: &mut usize) {
+    let mut offending = true;
+    while let Some(token) = tokens.get(*index) {
+        let same_line = *index > 0 && tokens[*index - 1].span().end().line() == token.span().start().line();
+        match token.kind() {
+            SEMICOLON | RIGHT_BRACE => {
+                *index += 1;
+                break;
+            },
+            INCLUDE | KEYWORDS | TOKEN_KIND | ATTRIBUTE | REGULAR_EXPRESSION | STRING_LITERAL if !(offending && same_line) => break,
+            _ => *index += 1,
+        }
+        offending = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Lexer;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn syntax_errors_are_located_and_parsing_recovers_at_the_next_production() -> Result<()> {
+        let error = Lexer::<String>::new("/a/ => A\n/b/ A;\n/ / => ;\n").err().ok_or("expected error")?;
+        assert_eq!("expected `;` after token kind", error.message());
+        assert_eq!(Some((2, 1)), error.span().map(|span| (span.start().line(), span.start().column())));
+        assert_eq!(Some("/b/"), error.text());
+        let errors = error.errors().map(|error| (error.message(), error.span().map(|span| span.start().line()))).collect::<Vec<_>>();
+        assert_eq!(vec![("expected `;` after token kind", Some(2)), ("expected `=>` after pattern", Some(2))], errors);
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    char,
+    cmp::Reverse,
+    collections::BTreeMap as Map,
+    iter::Peekable,
+    str::Chars,
+};
+use simple_lexer_bootstrap::{
+    Lexer as LexerBootstrap,
+    Rule,
+};
+use crate::{
+    error::Error,
+    grammar::{
+        Pattern,
+        Statement,
+        StatementKind,
+        as_definitions,
+        as_productions,
+        expand,
+    },
+    json,
+    spec::parse,
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+// Exports the productions and keyword tables of a spec as a TextMate grammar,
+// giving each token kind the scope it maps to. Along with the grammar come
+// the constructs which could not be exported faithfully: token kinds without
+// a scope, patterns matching line breaks, which TextMate never sees, and
+// contextual keywords. Skipped productions produce no tokens to scope, so
+// they are left out. TextMate tries patterns in order rather than taking the
+// longest match, so patterns are listed by descending priority and then in
+// spec order, and keywords come before everything else. Productions of the
+// same priority which match a common token are reported too, since TextMate
+// stops at the earlier one even where the later one would match longer.
+pub fn to_textmate(text: &str, scope_name: &str, scopes: &Map<String, String>) -> Result<(String, Vec<Error>)> {
+    let statements = parse(text)?;
+    let definitions = as_definitions(&statements)?;
+    let mut reports = first_matches(&statements)?;
+    let mut keywords = Vec::new();
+    let mut productions = Vec::new();
+    for statement in &statements {
+        match &statement.kind {
+            StatementKind::Production { pattern, token_kind: Some(token_kind), attributes } if !attributes.fragment && !attributes.skip => {
+                let scope = match scopes.get(token_kind) {
+                    Some(scope) => scope,
+                    None => {
+                        reports.push(statement.error(Error::new("no scope for token kind").found(token_kind)));
+                        continue;
+                    },
+                };
+                let regular_expression = match pattern {
+                    Pattern::RegularExpression(regular_expression) => {
+                        let expanded = expand(regular_expression, &definitions, &mut Vec::new()).map_err(|error| statement.error(error))?;
+                        translate(statement, &expanded, &mut reports)
+                    },
+                    Pattern::Literal(literal) => quote(literal),
+                };
+                productions.push((attributes.priority.unwrap_or(0), scope, regular_expression));
+            },
+            StatementKind::Keywords { keywords: table, attributes, .. } => {
+                if attributes.after.is_some() {
+                    reports.push(statement.error(Error::new("contextual keywords")));
+                    continue;
+                }
+                for (keyword, token_kind) in table {
+                    match scopes.get(token_kind) {
+                        Some(scope) => {
+                            let case = if attributes.caseless { "(?i)" } else { "" };
+                            keywords.push((0, scope, format!("{}\\b{}\\b", case, quote(keyword))));
+                        },
+                        None => reports.push(statement.error(Error::new("no scope for token kind").found(token_kind))),
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    productions.sort_by_key(|&(priority, _, _)| Reverse(priority));
+    let patterns: Vec<String> = keywords.into_iter().chain(productions).map(|(_, scope, regular_expression)| {
+        format!("    {{ \"name\": {}, \"match\": {} }}", json::string(scope), json::string(&regular_expression))
+    }).collect();
+    let grammar = format!("{{\n  \"scopeName\": {},\n  \"patterns\": [\n{}\n  ]\n}}\n", json::string(scope_name), patterns.join(",\n"));
+    Ok((grammar, reports))
+}
+
+// Reports each pair of productions which the lexer's ambiguity analysis finds
+// tying on priority, at the later production with a note at the earlier one.
+fn first_matches(statements: &[Statement]) -> Result<Vec<Error>> {
+    let (rules, _) = as_productions::<String>(statements)?;
+    let analysis = LexerBootstrap::from_rules(rules)?.analyze();
+    let production = |rule: &Rule<String>| statements.iter().find(|statement| {
+        matches!(statement.kind, StatementKind::Production { .. }) && Some(statement.span.start()) == rule.location()
+    });
+    let mut reports = Vec::new();
+    for (winner, loser) in analysis.ambiguous_rules() {
+        if let (Some(first), Some(later)) = (production(winner), production(loser)) {
+            let note = first.error(Error::new("earlier production here"));
+            reports.push(later.error(Error::new("first match may differ from longest match")).with_note(note));
+        }
+    }
+    Ok(reports)
+}
+
+// Rewrites a spec regular expression in Oniguruma syntax. Numeric escapes
+// become `\x{...}`, and `.` and `$`, which are plain characters in specs,
+// are escaped. A class matches line breaks if it names one, or if it is
+// negated and names none.
+fn translate(statement: &Statement, regular_expression: &str, reports: &mut Vec<Error>) -> String {
+    let mut translated = String::new();
+    let mut line_break = false;
+    // whether the class being read is negated, and whether it names a line
+    // break so far
+    let mut class: Option<(bool, bool)> = None;
+    let mut characters = regular_expression.chars().peekable();
+    while let Some(character) = characters.next() {
+        let named = match character {
+            '\\' => escape(&mut characters, &mut translated),
+            '.' | '$' => {
+                translated.push('\\');
+                translated.push(character);
+                false
+            },
+            '[' if class.is_none() => {
+                class = Some((characters.peek() == Some(&'^'), false));
+                translated.push(character);
+                false
+            },
+            ']' if class.is_some() => {
+                if let Some((negated, named)) = class.take() {
+                    line_break |= negated != named;
+                }
+                translated.push(character);
+                false
+            },
+            _ => {
+                translated.push(character);
+                false
+            },
+        };
+        match &mut class {
+            Some((_, class_named)) => *class_named |= named,
+            None => line_break |= named,
+        }
+    }
+    if line_break {
+        reports.push(statement.error(Error::new("pattern matches line breaks")));
+    }
+    translated
+}
+
+// Rewrites the escape following a backslash, returning whether it names a
+// line break.
+fn escape(characters: &mut Peekable<Chars>, translated: &mut String) -> bool {
+    let (radix, digits) = match characters.peek().cloned() {
+        Some('0'..='7') => (8, 3),
+        Some('x') => (16, 2),
+        Some('u') => (16, 4),
+        Some('U') => (16, 8),
+        Some(escaped) => {
+            characters.next();
+            translated.push('\\');
+            translated.push(escaped);
+            return escaped == 'n' || escaped == 'r';
+        },
+        None => {
+            translated.push('\\');
+            return false;
+        },
+    };
+    if radix == 16 {
+        characters.next();
+    }
+    let mut value = String::new();
+    while value.len() < digits && characters.peek().is_some_and(|digit| digit.is_digit(radix)) {
+        value.push(characters.next().expect("peeked character"));
+    }
+    let value = u32::from_str_radix(&value, radix).unwrap_or(0);
+    translated.push_str(&format!("\\x{{{:X}}}", value));
+    char::from_u32(value).is_some_and(|character| character == '\n' || character == '\r')
+}
+
+// Escapes a literal for Oniguruma.
+fn quote(literal: &str) -> String {
+    let mut quoted = String::new();
+    for character in literal.chars() {
+        if character.is_ascii_punctuation() && character != '_' {
+            quoted.push('\\');
+        }
+        quoted.push(character);
+    }
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_textmate;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[test]
+    fn textmate_grammars_put_keywords_first_and_report_what_they_cannot_express() -> Result<()> {
+        let scopes = map![
+            String::from("IDENTIFIER") => String::from("variable"),
+            String::from("FLOAT") => String::from("constant.numeric"),
+            String::from("PLUS") => String::from("keyword.operator"),
+            String::from("NEWLINE") => String::from("punctuation"),
+            String::from("IF") => String::from("keyword.control"),
+            String::from("STRING") => String::from("string.quoted"),
+            String::from("CHAR") => String::from("constant.character"),
+            String::from("COMMENT") => String::from("comment")
+        ];
+        let (grammar, reports) = to_textmate(r#"
+            /[a-z]+/ => IDENTIFIER;
+            /[0-9]+.[0-9]+/ => FLOAT;
+            "+" => PLUS;
+            /\n/ => NEWLINE;
+            / / => ;
+            keywords IDENTIFIER { "if" => IF }
+            @after("PLUS") keywords IDENTIFIER { "get" => GET }
+            /"[^"]*"/ => STRING;
+            /'[^'\n]'/ => CHAR;
+            @skip /#[a-z]*/ => COMMENT;
+        "#, "source.test", &scopes)?;
+        let expected = concat!(
+            "{\n",
+            "  \"scopeName\": \"source.test\",\n",
+            "  \"patterns\": [\n",
+            "    { \"name\": \"keyword.control\", \"match\": \"\\\\bif\\\\b\" },\n",
+            "    { \"name\": \"variable\", \"match\": \"[a-z]+\" },\n",
+            "    { \"name\": \"constant.numeric\", \"match\": \"[0-9]+\\\\.[0-9]+\" },\n",
+            "    { \"name\": \"keyword.operator\", \"match\": \"\\\\+\" },\n",
+            "    { \"name\": \"punctuation\", \"match\": \"\\\\n\" },\n",
+            "    { \"name\": \"string.quoted\", \"match\": \"\\\"[^\\\"]*\\\"\" },\n",
+            "    { \"name\": \"constant.character\", \"match\": \"'[^'\\\\n]'\" }\n",
+            "  ]\n",
+            "}\n",
+        );
+        assert_eq!(expected, grammar);
+        let reports = reports.iter().map(|report| (report.message(), report.span().map(|span| span.start().line()))).collect::<Vec<_>>();
+        assert_eq!(vec![("pattern matches line breaks", Some(5)), ("contextual keywords", Some(8)), ("pattern matches line breaks", Some(9))], reports);
+        Ok(())
+    }
+
+    #[test]
+    fn textmate_reports_same_priority_productions_it_resolves_by_first_match() -> Result<()> {
+        let scopes = map![
+            String::from("IF") => String::from("keyword.control"),
+            String::from("IDENTIFIER") => String::from("variable")
+        ];
+        let (grammar, reports) = to_textmate(r#"
+            "if" => IF;
+            /[a-z]+/ => IDENTIFIER;
+            / / => ;
+        "#, "source.test", &scopes)?;
+        assert!(grammar.find("keyword.control") < grammar.find("variable"));
+        let reports = reports.iter().map(|report| {
+            (report.message(), report.span().map(|span| span.start().line()), report.note().and_then(|note| note.span()).map(|span| span.start().line()))
+        }).collect::<Vec<_>>();
+        assert_eq!(vec![("first match may differ from longest match", Some(3), Some(2))], reports);
+        Ok(())
+    }
+}
@@ -7,3 +7,33 @@ macro_rules! map {
     }}
 }
 
+
+// Tokens of the given kinds and texts. Tokens compare by kind and text alone,
+// so these equal the tokens a Lexer<String> produces.
+#[cfg(test)]
+pub(crate) fn tokens(pairs: &[(&str, &str)]) -> Vec<crate::Token<String>> {
+    pairs.iter().map(|&(kind, text)| crate::Token::new(String::from(kind), text)).collect()
+}
+
+// A temporary directory which is removed when dropped, so a failing test
+// does not leave it behind.
+#[cfg(test)]
+pub(crate) struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub(crate) fn new(name: &str) -> TempDir {
+        TempDir(std::env::temp_dir().join(format!("{}-{}", name, std::process::id())))
+    }
+
+    pub(crate) fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}